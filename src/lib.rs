@@ -5,13 +5,10 @@
 //! Example usage:
 //!
 //! ```rust,no_run
-//! extern crate monzo;
-//! extern crate tokio_core;
-//!
-//! let mut core = tokio_core::reactor::Core::new().unwrap();
+//! let runtime = tokio::runtime::Runtime::new().unwrap();
 //! let monzo = monzo::Client::new("<access_token>");
 //! let work = monzo.balance("<account_id>".into());
-//! let response = core.run(work).unwrap();
+//! let response = runtime.block_on(work).unwrap();
 //! println!("Balance: {} {}", response.balance, response.currency);
 //! println!("Spent today: {}", response.spend_today);
 //! ```
@@ -25,29 +22,35 @@
 
 #[macro_use]
 extern crate error_chain;
-extern crate chrono;
-extern crate futures;
-extern crate hyper;
-extern crate hyper_tls;
-extern crate serde;
 #[macro_use]
 extern crate serde_derive;
-extern crate serde_json;
-extern crate url;
-
-use chrono::offset::Utc;
-use chrono::DateTime;
-use futures::{Future, Stream};
-use hyper::header::AUTHORIZATION;
-use hyper::{Body, Chunk, Request, StatusCode, Uri};
+
+use chrono::offset::{TimeZone, Utc};
+use chrono::{DateTime, SecondsFormat};
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
+use hyper::body::{Bytes, HttpBody};
+use hyper::client::connect::Connect;
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use hyper::http::request;
+use hyper::{Body, Method, Request, Response, StatusCode, Uri};
+use hyper_tls::HttpsConnector;
 use serde::de;
 use serde::de::Deserialize;
 use serde::de::Deserializer;
 use serde::de::Visitor;
-use std::collections::HashMap;
+use serde::ser::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::future::Future;
+use std::io;
 use std::str::FromStr;
 use std::string::String;
-use url::Url;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use url::{form_urlencoded, Url};
+use uuid::Uuid;
 
 /// Identifier for an account.
 pub type AccountId = String;
@@ -55,29 +58,300 @@ pub type AccountId = String;
 pub type TransactionId = String;
 /// Identifier of a merchant.
 pub type MerchantId = String;
-/// Holds an ISO 4217 currency code.
-pub type Currency = String;
+/// Identifier of a pot.
+pub type PotId = String;
+/// Identifier of a webhook.
+pub type WebhookId = String;
+/// Identifier of an attachment.
+pub type AttachmentId = String;
+/// An ISO 4217 currency code. Codes without a variant of their own are kept in `Other`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum Currency {
+    /// Pound sterling.
+    Gbp,
+    /// Euro.
+    Eur,
+    /// United States dollar.
+    Usd,
+    /// Japanese yen.
+    Jpy,
+    /// Swiss franc.
+    Chf,
+    /// Any other currency, holding its code.
+    Other(String),
+}
+
+impl Currency {
+    /// The ISO 4217 code of the currency, eg. `GBP`.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            Currency::Gbp => "GBP",
+            Currency::Eur => "EUR",
+            Currency::Usd => "USD",
+            Currency::Jpy => "JPY",
+            Currency::Chf => "CHF",
+            Currency::Other(ref code) => code,
+        }
+    }
+
+    /// Number of digits after the decimal point in the major unit of the currency, as listed in
+    /// ISO 4217, eg. 2 for GBP, 0 for JPY and 3 for KWD.
+    pub fn decimal_places(&self) -> u32 {
+        match self.as_str() {
+            "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+            | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+            "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+            _ => 2,
+        }
+    }
+
+    fn symbol(&self) -> Option<&'static str> {
+        match *self {
+            Currency::Gbp => Some("£"),
+            Currency::Eur => Some("€"),
+            Currency::Usd => Some("$"),
+            Currency::Jpy => Some("¥"),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for Currency {
+    fn from(code: String) -> Currency {
+        match code.as_str() {
+            "GBP" => Currency::Gbp,
+            "EUR" => Currency::Eur,
+            "USD" => Currency::Usd,
+            "JPY" => Currency::Jpy,
+            "CHF" => Currency::Chf,
+            _ => Currency::Other(code),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Currency {
+    fn from(code: &'a str) -> Currency {
+        Currency::from(code.to_string())
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> String {
+        currency.as_str().to_string()
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Idempotency key for calls that move money. Monzo performs a call only once for the same key,
+/// so retrying with the same `DedupeId` is safe.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DedupeId(String);
+
+impl DedupeId {
+    /// Creates a new random id, to use for a single operation and its retries.
+    pub fn random() -> DedupeId {
+        DedupeId(Uuid::new_v4().to_string())
+    }
+
+    /// The id as sent to Monzo.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for DedupeId {
+    fn from(id: String) -> DedupeId {
+        DedupeId(id)
+    }
+}
+
+impl<'a> From<&'a str> for DedupeId {
+    fn from(id: &'a str) -> DedupeId {
+        DedupeId(id.to_string())
+    }
+}
+
+/// An amount in minor units of a currency, eg. pennies for GBP.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Money {
+    /// The amount in minor units of the currency.
+    pub amount: i64,
+    /// The ISO 4217 currency code.
+    pub currency: Currency,
+}
+
+impl Money {
+    /// Creates an amount of money from minor units of the currency.
+    pub fn new(amount: i64, currency: Currency) -> Money {
+        Money { amount, currency }
+    }
+
+    /// Creates an amount of money from major units of the currency, eg. pounds for GBP, rounded to
+    /// the nearest minor unit.
+    pub fn from_major(major: f64, currency: Currency) -> Money {
+        let factor = 10f64.powi(currency.decimal_places() as i32);
+        Money::new((major * factor).round() as i64, currency)
+    }
+
+    /// No money in the given currency, the start of a total.
+    pub fn zero(currency: Currency) -> Money {
+        Money::new(0, currency)
+    }
+
+    /// Adds two amounts of money, or returns `None` if their currencies differ or the sum
+    /// overflows.
+    pub fn checked_add(&self, other: &Money) -> Option<Money> {
+        if self.currency != other.currency {
+            return None;
+        }
+        let amount = self.amount.checked_add(other.amount)?;
+        Some(Money::new(amount, self.currency.clone()))
+    }
+
+    /// Adds up amounts of money in the given currency, or returns `None` if any of them is in
+    /// another currency. Nothing adds up to zero.
+    pub fn sum<I>(currency: Currency, amounts: I) -> Option<Money>
+    where
+        I: IntoIterator<Item = Money>,
+    {
+        amounts
+            .into_iter()
+            .try_fold(Money::zero(currency), |total, money| total.checked_add(&money))
+    }
+
+    /// The amount in major units of the currency, eg. pounds for GBP.
+    pub fn major_units(&self) -> f64 {
+        self.amount as f64 / 10f64.powi(self.currency.decimal_places() as i32)
+    }
+
+    // The absolute amount in major units with all decimal places, eg. `5.10`.
+    fn unsigned_major_units(&self) -> String {
+        let places = self.currency.decimal_places();
+        let minor = self.amount.unsigned_abs();
+        if places == 0 {
+            minor.to_string()
+        } else {
+            let factor = 10u64.pow(places);
+            format!("{}.{:02$}", minor / factor, minor % factor, places as usize)
+        }
+    }
+}
+
+impl fmt::Display for Money {
+    /// Formats the amount with the currency symbol, eg. `-£5.10`. Currencies without a known
+    /// symbol are followed by their code instead, eg. `5.10 CHF`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.amount < 0 { "-" } else { "" };
+        let number = self.unsigned_major_units();
+        match self.currency.symbol() {
+            Some(symbol) => write!(f, "{}{}{}", sign, symbol, number),
+            None => write!(f, "{}{} {}", sign, number, self.currency),
+        }
+    }
+}
 
 /// Accounts represent a store of funds, and have a list of transactions.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub struct Account {
     /// The account id.
     pub id: AccountId,
     /// Description of the account.
     pub description: String,
     /// The timestamp when the account was created.
+    #[serde(deserialize_with = "rfc3339_or_epoch")]
     pub created: DateTime<Utc>,
+    /// The ISO 4217 currency code of the account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Currency>,
+    /// The ISO 3166 country code of the account, eg. `GB`.
+    #[serde(
+        default,
+        deserialize_with = "none_for_empty_string",
+        serialize_with = "empty_string_for_none"
+    )]
+    pub country_code: Option<String>,
+    /// The type of the account, eg. `uk_retail` or `uk_retail_joint`.
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub account_type: Option<String>,
+    /// The account number. Monzo sends an empty string for accounts without one, like prepaid
+    /// accounts.
+    #[serde(
+        default,
+        deserialize_with = "none_for_empty_string",
+        serialize_with = "empty_string_for_none"
+    )]
+    pub account_number: Option<String>,
+    /// The sort code, without dashes. Empty for accounts without one, like the account number.
+    #[serde(
+        default,
+        deserialize_with = "none_for_empty_string",
+        serialize_with = "empty_string_for_none"
+    )]
+    pub sort_code: Option<String>,
+    /// The users owning the account. Joint accounts have two, other accounts may leave it out.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<Owner>,
+    /// Whether the account has been closed.
+    #[serde(default)]
+    pub closed: bool,
+}
+
+/// A user owning an account.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Owner {
+    /// The id of the user.
+    pub user_id: String,
+    /// The full name the user goes by.
+    pub preferred_name: String,
+    /// The first name the user goes by.
+    pub preferred_first_name: String,
 }
 
 /// Response to the list accounts future.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub struct Accounts {
     /// List of accounts owned by the currently authorized user.
     pub accounts: Vec<Account>,
 }
 
+/// Response to the whoami future if successful.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct WhoAmI {
+    /// Whether the access token is authenticated.
+    pub authenticated: bool,
+    /// The client id the access token was issued to.
+    pub client_id: String,
+    /// The user the access token belongs to.
+    pub user_id: String,
+}
+
+/// An access token issued by the OAuth token endpoint, in response to the exchange_code and
+/// refresh_token futures if successful.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct AccessToken {
+    /// The token to pass to `Client::new`.
+    pub access_token: String,
+    /// The client id the access token was issued to.
+    pub client_id: String,
+    /// Number of seconds until the access token expires.
+    pub expires_in: u64,
+    /// Token to get a new access token with once this one expires. Only confidential clients get
+    /// one.
+    pub refresh_token: Option<String>,
+    /// The type of the token, usually `Bearer`.
+    pub token_type: String,
+    /// The user the access token belongs to.
+    pub user_id: String,
+}
+
 /// Response to the balance future if successful.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub struct Balance {
     /// The currently available balance of the account, as a 64bit integer in minor units of the
     /// currency, eg. pennies for GBP, or cents for EUR and USD.
@@ -89,10 +363,28 @@ pub struct Balance {
     pub spend_today: i64,
 }
 
+impl Balance {
+    /// Whether the balance is negative, which current accounts with an arranged overdraft can be.
+    pub fn is_overdrawn(&self) -> bool {
+        self.balance < 0
+    }
+
+    /// The balance together with its currency.
+    pub fn balance_money(&self) -> Money {
+        Money::new(self.balance, self.currency.clone())
+    }
+
+    /// The amount spent today together with its currency.
+    pub fn spend_today_money(&self) -> Money {
+        Money::new(self.spend_today, self.currency.clone())
+    }
+}
+
 /// Deserializes a string but returns None on empty string.
 fn none_for_empty_string<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
 where
     T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
     D: Deserializer<'de>,
 {
     // This is a Visitor that forwards string types to T's `FromStr` impl if the string is
@@ -103,10 +395,11 @@ where
     impl<'de, T> Visitor<'de> for NonEmptyString<T>
     where
         T: Deserialize<'de> + FromStr,
+        T::Err: fmt::Display,
     {
         type Value = Option<T>;
 
-        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
             formatter.write_str("string")
         }
 
@@ -115,13 +408,14 @@ where
             E: de::Error,
         {
             if value.is_empty() {
-                return Ok(None);
+                Ok(None)
             } else {
-                let res = FromStr::from_str(value);
-                match res {
+                match T::from_str(value) {
                     Ok(good) => Ok(Some(good)),
-                    // TODO: Find a way to propagate the error.
-                    Err(_) => Err(de::Error::custom("could not parse string")),
+                    Err(e) => Err(de::Error::custom(format!(
+                        "could not parse string '{}': {}",
+                        value, e
+                    ))),
                 }
             }
         }
@@ -137,8 +431,255 @@ where
     deserializer.deserialize_any(NonEmptyString(std::marker::PhantomData))
 }
 
+/// Serializes None as an empty string, the counterpart of `none_for_empty_string`.
+fn empty_string_for_none<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    match *value {
+        Some(ref value) => value.serialize(serializer),
+        None => serializer.serialize_str(""),
+    }
+}
+
+/// Deserializes a timestamp from either an RFC 3339 string or a Unix epoch integer. Integers from
+/// 10^11 onwards are taken to be milliseconds, since in seconds that is over a thousand years out.
+fn rfc3339_or_epoch<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct Rfc3339OrEpoch;
+
+    impl<'de> Visitor<'de> for Rfc3339OrEpoch {
+        type Value = DateTime<Utc>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("RFC 3339 string or Unix timestamp")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<DateTime<Utc>, E>
+        where
+            E: de::Error,
+        {
+            value
+                .parse()
+                .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(value), &self))
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<DateTime<Utc>, E>
+        where
+            E: de::Error,
+        {
+            let (seconds, nanos) = if value.abs() >= 100_000_000_000 {
+                (
+                    value.div_euclid(1000),
+                    value.rem_euclid(1000) as u32 * 1_000_000,
+                )
+            } else {
+                (value, 0)
+            };
+            Utc.timestamp_opt(seconds, nanos)
+                .single()
+                .ok_or_else(|| de::Error::invalid_value(de::Unexpected::Signed(value), &self))
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<DateTime<Utc>, E>
+        where
+            E: de::Error,
+        {
+            if value > i64::MAX as u64 {
+                return Err(de::Error::invalid_value(
+                    de::Unexpected::Unsigned(value),
+                    &self,
+                ));
+            }
+            self.visit_i64(value as i64)
+        }
+    }
+
+    deserializer.deserialize_any(Rfc3339OrEpoch)
+}
+
+/// Deserializes a string map, but also accepts an empty array as an empty map. Monzo sometimes
+/// sends `[]` instead of `{}` for empty metadata.
+fn map_or_empty_array<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct MapOrEmptyArray;
+
+    impl<'de> Visitor<'de> for MapOrEmptyArray {
+        type Value = HashMap<String, String>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("map or empty array")
+        }
+
+        fn visit_map<A>(self, mut access: A) -> Result<HashMap<String, String>, A::Error>
+        where
+            A: de::MapAccess<'de>,
+        {
+            let mut map = HashMap::with_capacity(access.size_hint().unwrap_or(0));
+            while let Some((key, value)) = access.next_entry()? {
+                map.insert(key, value);
+            }
+            Ok(map)
+        }
+
+        fn visit_seq<A>(self, mut access: A) -> Result<HashMap<String, String>, A::Error>
+        where
+            A: de::SeqAccess<'de>,
+        {
+            match access.next_element::<de::IgnoredAny>()? {
+                None => Ok(HashMap::new()),
+                Some(_) => Err(de::Error::invalid_length(1, &"an empty array")),
+            }
+        }
+    }
+
+    deserializer.deserialize_any(MapOrEmptyArray)
+}
+
+/// The category of a transaction.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum Category {
+    /// general
+    General,
+    /// eating_out
+    EatingOut,
+    /// expenses
+    Expenses,
+    /// transport
+    Transport,
+    /// cash
+    Cash,
+    /// bills
+    Bills,
+    /// entertainment
+    Entertainment,
+    /// shopping
+    Shopping,
+    /// holidays
+    Holidays,
+    /// groceries
+    Groceries,
+    /// mondo, the category of top-ups.
+    Mondo,
+    /// Any category this crate doesn't know about yet, holding the name Monzo sent.
+    Other(String),
+}
+
+impl Category {
+    /// The name Monzo uses for the category, for example "eating_out".
+    pub fn as_str(&self) -> &str {
+        match *self {
+            Category::General => "general",
+            Category::EatingOut => "eating_out",
+            Category::Expenses => "expenses",
+            Category::Transport => "transport",
+            Category::Cash => "cash",
+            Category::Bills => "bills",
+            Category::Entertainment => "entertainment",
+            Category::Shopping => "shopping",
+            Category::Holidays => "holidays",
+            Category::Groceries => "groceries",
+            Category::Mondo => "mondo",
+            Category::Other(ref name) => name,
+        }
+    }
+
+    /// An emoji to show next to the category, like the Monzo app does. Unknown categories get a
+    /// generic one.
+    pub fn emoji(&self) -> &'static str {
+        match *self {
+            Category::General => "💳",
+            Category::EatingOut => "🍽",
+            Category::Expenses => "💼",
+            Category::Transport => "🚌",
+            Category::Cash => "💵",
+            Category::Bills => "💡",
+            Category::Entertainment => "🎉",
+            Category::Shopping => "🛍",
+            Category::Holidays => "🏖",
+            Category::Groceries => "🛒",
+            Category::Mondo => "💰",
+            Category::Other(_) => "🏷",
+        }
+    }
+}
+
+impl From<String> for Category {
+    fn from(name: String) -> Category {
+        match name.as_str() {
+            "general" => Category::General,
+            "eating_out" => Category::EatingOut,
+            "expenses" => Category::Expenses,
+            "transport" => Category::Transport,
+            "cash" => Category::Cash,
+            "bills" => Category::Bills,
+            "entertainment" => Category::Entertainment,
+            "shopping" => Category::Shopping,
+            "holidays" => Category::Holidays,
+            "groceries" => Category::Groceries,
+            "mondo" => Category::Mondo,
+            _ => Category::Other(name),
+        }
+    }
+}
+
+impl From<Category> for String {
+    fn from(category: Category) -> String {
+        category.as_str().to_string()
+    }
+}
+
+/// Why a transaction was declined.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum DeclineReason {
+    /// INSUFFICIENT_FUNDS
+    InsufficientFunds,
+    /// CARD_INACTIVE
+    CardInactive,
+    /// CARD_BLOCKED
+    CardBlocked,
+    /// Any other reason, including OTHER, holding the reason Monzo sent.
+    Other(String),
+}
+
+impl DeclineReason {
+    /// The name Monzo uses for the reason, for example "CARD_INACTIVE".
+    pub fn as_str(&self) -> &str {
+        match *self {
+            DeclineReason::InsufficientFunds => "INSUFFICIENT_FUNDS",
+            DeclineReason::CardInactive => "CARD_INACTIVE",
+            DeclineReason::CardBlocked => "CARD_BLOCKED",
+            DeclineReason::Other(ref reason) => reason,
+        }
+    }
+}
+
+impl From<String> for DeclineReason {
+    fn from(reason: String) -> DeclineReason {
+        match reason.as_str() {
+            "INSUFFICIENT_FUNDS" => DeclineReason::InsufficientFunds,
+            "CARD_INACTIVE" => DeclineReason::CardInactive,
+            "CARD_BLOCKED" => DeclineReason::CardBlocked,
+            _ => DeclineReason::Other(reason),
+        }
+    }
+}
+
+impl From<DeclineReason> for String {
+    fn from(reason: DeclineReason) -> String {
+        reason.as_str().to_string()
+    }
+}
+
 /// Describes a transaction.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Transaction {
     /// Balance in the account after the transaction.
     pub account_balance: i64,
@@ -147,16 +688,27 @@ pub struct Transaction {
     /// amount).
     pub amount: i64,
     /// The timestamp in when the transaction was created.
+    #[serde(deserialize_with = "rfc3339_or_epoch")]
     pub created: DateTime<Utc>,
     /// The ISO 4217 currency code.
     pub currency: Currency,
+    /// The amount in minor units of the currency the purchase was made in. Only present on
+    /// foreign transactions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_amount: Option<i64>,
+    /// The ISO 4217 currency code the purchase was made in. Only present on foreign
+    /// transactions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_currency: Option<Currency>,
     /// Description of the transaction.
     pub description: String,
     /// The transaction id.
     pub id: TransactionId,
-    /// This contains the merchant_id of the merchant that this transaction was made at.
-    pub merchant: Option<MerchantId>,
+    /// The merchant that this transaction was made at. Only the merchant id unless the merchant
+    /// was expanded, see `TransactionsQuery::expand_merchant`.
+    pub merchant: Option<MerchantInfo>,
     /// Key-value annotations made for transaction. Metadata is private to your application.
+    #[serde(deserialize_with = "map_or_empty_array")]
     pub metadata: HashMap<String, String>,
     /// Notes attached to the transaction.
     pub notes: String,
@@ -170,39 +722,489 @@ pub struct Transaction {
     ///
     /// Bug: Even though the Monzo documentation says the field is not present when not authorised,
     /// in practice they send an empty string. See https://github.com/monzo/docs/pull/59.
-    #[serde(deserialize_with = "none_for_empty_string")]
+    #[serde(
+        deserialize_with = "none_for_empty_string",
+        serialize_with = "empty_string_for_none"
+    )]
     pub settled: Option<DateTime<Utc>>,
     /// The category can be set for each transaction by the user. Over time we learn which merchant
     /// goes in which category and auto-assign the category of a transaction. If the user hasn’t
     /// set a category, we’ll return the default category of the merchant on this transactions.
-    /// Top-ups have category mondo. Valid values are general, eating_out, expenses, transport,
-    /// cash, bills, entertainment, shopping, holidays, groceries.
-    pub category: String,
-    /// This is only present on declined transactions! Valid values are INSUFFICIENT_FUNDS,
-    /// CARD_INACTIVE, CARD_BLOCKED or OTHER.
-    // TODO: Make this an enum?
-    pub decline_reason: Option<String>,
+    /// Top-ups have category mondo.
+    pub category: Category,
+    /// This is only present on declined transactions!
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decline_reason: Option<DeclineReason>,
+    /// The payment scheme the transaction went through, for example mastercard,
+    /// payport_faster_payments, bacs or uk_retail_pot.
+    #[serde(default)]
+    pub scheme: String,
+    /// The user who made the transaction, which matters on joint accounts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// Whether the authenticated user made the transaction, rather than the other owner of a
+    /// joint account or a merchant charging a subscription.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub originator: Option<bool>,
+    /// Whether the transaction can be added to a tab.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub can_add_to_tab: Option<bool>,
+    /// Whether the transaction can be excluded from the spending breakdown.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub can_be_excluded_from_breakdown: Option<bool>,
+    /// Whether the transaction can be made into a subscription.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub can_be_made_subscription: Option<bool>,
+    /// Whether the bill of the transaction can be split.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub can_split_the_bill: Option<bool>,
+    /// The other side of a bank transfer. Card transactions send an empty object, which parses
+    /// to a counterparty with all fields None.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counterparty: Option<Counterparty>,
+}
+
+/// The sender or recipient of a bank transfer.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Counterparty {
+    /// The account number of the other account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_number: Option<String>,
+    /// The sort code of the other account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_code: Option<String>,
+    /// The name of the account holder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The Monzo user id, when the other side banks with Monzo too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+/// Describes a merchant.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Merchant {
+    /// The merchant id.
+    pub id: MerchantId,
+    /// Id of the group of merchants this merchant belongs to, for example all branches of a chain.
+    pub group_id: String,
+    /// The timestamp when the merchant was created.
+    #[serde(deserialize_with = "rfc3339_or_epoch")]
+    pub created: DateTime<Utc>,
+    /// Name of the merchant.
+    pub name: String,
+    /// Url of the merchant's logo.
+    pub logo: String,
+    /// Emoji representing the merchant.
+    pub emoji: String,
+    /// The default category of transactions at this merchant.
+    pub category: Category,
+    /// Where the merchant is located.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<MerchantAddress>,
+    /// Extra information about the merchant, like `website`, `suggested_tags` and whether it is
+    /// `online`.
+    #[serde(default, deserialize_with = "map_or_empty_array")]
+    pub metadata: HashMap<String, String>,
+}
+
+/// The address of a merchant. Online merchants often lack most of it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MerchantAddress {
+    /// The address on a single line.
+    pub short_formatted: String,
+    /// The address on multiple lines.
+    pub formatted: String,
+    /// The city, if known.
+    #[serde(
+        default,
+        deserialize_with = "none_for_empty_string",
+        serialize_with = "empty_string_for_none"
+    )]
+    pub city: Option<String>,
+    /// Latitude of the merchant, if known.
+    pub latitude: Option<f64>,
+    /// Longitude of the merchant, if known.
+    pub longitude: Option<f64>,
+    /// The postcode, if known.
+    #[serde(
+        default,
+        deserialize_with = "none_for_empty_string",
+        serialize_with = "empty_string_for_none"
+    )]
+    pub postcode: Option<String>,
+    /// The region, if known.
+    #[serde(
+        default,
+        deserialize_with = "none_for_empty_string",
+        serialize_with = "empty_string_for_none"
+    )]
+    pub region: Option<String>,
+    /// The ISO 3166 country code, eg. `GBR`.
+    pub country: String,
+}
+
+/// The merchant of a transaction.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum MerchantInfo {
+    /// Just the id, when the merchant was not expanded.
+    Id(MerchantId),
+    /// The full merchant, when it was expanded.
+    Details(Box<Merchant>),
+}
+
+/// The kind of a transaction, as derived by `Transaction::transaction_type`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionType {
+    /// A card payment.
+    Purchase,
+    /// Money loaded into the account.
+    TopUp,
+    /// Money coming back for an earlier payment.
+    Refund,
+    /// Money moving between the account and one of its pots.
+    PotTransfer,
+    /// A faster payment or bacs transfer.
+    BankTransfer,
+    /// A cash withdrawal.
+    Atm,
+    /// A transaction that was declined and didn't move any money.
+    Declined,
+}
+
+impl Transaction {
+    /// The signed amount of the transaction together with its currency. Money leaving the
+    /// account is negative.
+    pub fn money(&self) -> Money {
+        Money::new(self.amount, self.currency.clone())
+    }
+
+    /// The signed amount in the currency the transaction was made in, eg. euros for a card
+    /// payment abroad, if Monzo reported one.
+    pub fn local_money(&self) -> Option<Money> {
+        match (self.local_amount, &self.local_currency) {
+            (Some(amount), Some(currency)) => Some(Money::new(amount, currency.clone())),
+            _ => None,
+        }
+    }
+
+    /// Whether the transaction was declined. Declined transactions don't move any money.
+    pub fn is_declined(&self) -> bool {
+        self.decline_reason.is_some()
+    }
+
+    /// Whether the transaction has settled. Pending card payments aren't settled yet.
+    pub fn is_settled(&self) -> bool {
+        self.settled.is_some()
+    }
+
+    /// The id of the merchant this transaction was made at, whether it was expanded or not.
+    pub fn merchant_id(&self) -> Option<&MerchantId> {
+        match self.merchant {
+            Some(MerchantInfo::Id(ref id)) => Some(id),
+            Some(MerchantInfo::Details(ref merchant)) => Some(&merchant.id),
+            None => None,
+        }
+    }
+
+    /// Derives the kind of transaction. The first matching rule wins:
+    ///
+    /// 1. `Declined` if there is a `decline_reason`.
+    /// 2. `PotTransfer` if the scheme is `uk_retail_pot`.
+    /// 3. `BankTransfer` if the scheme is `payport_faster_payments` or `bacs`.
+    /// 4. `TopUp` if `is_load` is set.
+    /// 5. `Atm` if the category is `cash`.
+    /// 6. `Refund` if the amount is positive.
+    /// 7. `Purchase` otherwise.
+    pub fn transaction_type(&self) -> TransactionType {
+        if self.is_declined() {
+            TransactionType::Declined
+        } else if self.scheme == "uk_retail_pot" {
+            TransactionType::PotTransfer
+        } else if self.scheme == "payport_faster_payments" || self.scheme == "bacs" {
+            TransactionType::BankTransfer
+        } else if self.is_load {
+            TransactionType::TopUp
+        } else if self.category == Category::Cash {
+            TransactionType::Atm
+        } else if self.amount > 0 {
+            TransactionType::Refund
+        } else {
+            TransactionType::Purchase
+        }
+    }
+}
+
+impl fmt::Display for Transaction {
+    // For example "-£5.10 THE DE BEAUVOIR DELI C LONDON GBR (eating_out)".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} ({})",
+            self.money(),
+            self.description,
+            self.category.as_str()
+        )
+    }
+}
+
+/// The maximum length in bytes of the notes on a transaction. Longer notes are rejected by Monzo.
+pub const MAX_NOTES_LENGTH: usize = 5000;
+
+/// Truncates notes to at most `MAX_NOTES_LENGTH` bytes, without splitting a multibyte character.
+pub fn truncate_notes(notes: &str) -> String {
+    if notes.len() <= MAX_NOTES_LENGTH {
+        return notes.to_string();
+    }
+    let mut end = MAX_NOTES_LENGTH;
+    while !notes.is_char_boundary(end) {
+        end -= 1;
+    }
+    notes[..end].to_string()
 }
 
 /// Response to the transactions future if successful.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Transactions {
     /// List of transactions.
     pub transactions: Vec<Transaction>,
 }
 
+impl Transactions {
+    // Debits that actually moved money, i.e. not the declined ones.
+    fn spends(&self) -> impl Iterator<Item = &Transaction> {
+        self.transactions
+            .iter()
+            .filter(|t| t.amount < 0 && t.decline_reason.is_none())
+    }
+
+    /// Counts the debits per range of `bucket_size` minor units of their absolute amount. Each
+    /// bucket is keyed by its lower bound, so with a `bucket_size` of 500 a debit of -510 lands in
+    /// bucket 500. Returns an empty histogram if `bucket_size` is not positive.
+    pub fn amount_histogram(&self, bucket_size: i64) -> BTreeMap<i64, usize> {
+        let mut histogram = BTreeMap::new();
+        if bucket_size <= 0 {
+            return histogram;
+        }
+        for t in self.spends() {
+            let bucket = (-t.amount) / bucket_size * bucket_size;
+            *histogram.entry(bucket).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Sums the debits per merchant, as a positive amount in minor units of the currency.
+    /// Transactions without a merchant are skipped.
+    pub fn spend_by_merchant(&self) -> HashMap<MerchantId, i64> {
+        let mut totals = HashMap::new();
+        for t in self.spends() {
+            if let Some(merchant) = t.merchant_id() {
+                *totals.entry(merchant.clone()).or_insert(0) -= t.amount;
+            }
+        }
+        totals
+    }
+
+    /// Sums the debits per category, as a positive amount in minor units of the currency, like
+    /// `spend_by_merchant`. Top-ups and declined transactions are skipped.
+    pub fn spend_by_category(&self) -> HashMap<Category, i64> {
+        let mut totals = HashMap::new();
+        for t in self.spends().filter(|t| !t.is_load) {
+            *totals.entry(t.category.clone()).or_insert(0) -= t.amount;
+        }
+        totals
+    }
+
+    /// Summarises the debits per category, biggest total first. Top-ups and declined transactions
+    /// are skipped, as in `spend_by_category`.
+    pub fn category_summary(&self) -> Vec<CategorySummary> {
+        let mut summaries: HashMap<&Category, (usize, i64)> = HashMap::new();
+        for t in self.spends().filter(|t| !t.is_load) {
+            let summary = summaries.entry(&t.category).or_insert((0, 0));
+            summary.0 += 1;
+            summary.1 -= t.amount;
+        }
+        let mut summaries: Vec<CategorySummary> = summaries
+            .into_iter()
+            .map(|(category, (count, total_minor))| CategorySummary {
+                category: category.clone(),
+                count,
+                total_minor,
+                average_minor: total_minor / count as i64,
+            })
+            .collect();
+        summaries.sort_by(|a, b| {
+            b.total_minor
+                .cmp(&a.total_minor)
+                .then_with(|| a.category.as_str().cmp(b.category.as_str()))
+        });
+        summaries
+    }
+
+    /// Returns the transactions in the given category.
+    pub fn filter_by_category(&self, category: Category) -> Vec<&Transaction> {
+        self.transactions
+            .iter()
+            .filter(|t| t.category == category)
+            .collect()
+    }
+
+    /// Returns the transactions with an amount between `min` and `max` minor units, inclusive.
+    /// Debits are negative, so `in_amount_range(-10000, -5000)` finds purchases of £50 to £100.
+    pub fn in_amount_range(&self, min: i64, max: i64) -> Vec<&Transaction> {
+        self.transactions
+            .iter()
+            .filter(|t| t.amount >= min && t.amount <= max)
+            .collect()
+    }
+
+    /// Returns every declined transaction together with its decline reason.
+    pub fn declines(&self) -> Vec<(&Transaction, &DeclineReason)> {
+        self.transactions
+            .iter()
+            .filter_map(|t| t.decline_reason.as_ref().map(|reason| (t, reason)))
+            .collect()
+    }
+
+    /// Counts the declined transactions per decline reason.
+    pub fn decline_counts(&self) -> HashMap<DeclineReason, usize> {
+        let mut counts = HashMap::new();
+        for (_, reason) in self.declines() {
+            *counts.entry(reason.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Exports the transactions as a QIF bank account, for importing into accounting software like
+    /// GnuCash or Quicken. Each entry has the date it was created, the amount in major units with
+    /// debits negative, the description as payee and the notes as memo. Declined transactions
+    /// didn't move money, so they are left out.
+    pub fn to_qif(&self) -> String {
+        // Every field is a single line.
+        fn line(text: &str) -> String {
+            text.replace(['\r', '\n'], " ")
+        }
+        let mut qif = String::from("!Type:Bank\n");
+        for t in self.transactions.iter().filter(|t| !t.is_declined()) {
+            let money = t.money();
+            let sign = if money.amount < 0 { "-" } else { "" };
+            qif.push_str(&format!("D{}\n", t.created.format("%m/%d/%Y")));
+            qif.push_str(&format!("T{}{}\n", sign, money.unsigned_major_units()));
+            qif.push_str(&format!("P{}\n", line(&t.description)));
+            if !t.notes.is_empty() {
+                qif.push_str(&format!("M{}\n", line(&t.notes)));
+            }
+            qif.push_str("^\n");
+        }
+        qif
+    }
+
+    /// Removes transactions with an id that was seen before, keeping the first occurrence and
+    /// the order of the rest. Useful after merging overlapping lists.
+    pub fn dedupe(&mut self) {
+        let mut seen = HashSet::new();
+        self.transactions.retain(|t| seen.insert(t.id.clone()));
+    }
+}
+
+/// Where to start listing transactions from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Since {
+    /// Transactions created after this timestamp.
+    Time(DateTime<Utc>),
+    /// Transactions after the transaction with this id, for paging through the list.
+    Cursor(TransactionId),
+}
+
+impl Since {
+    // The value of the since param on requests.
+    fn to_param(&self) -> String {
+        match *self {
+            Since::Time(time) => time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            Since::Cursor(ref transaction_id) => transaction_id.clone(),
+        }
+    }
+}
+
+/// The debits in one category, see `Transactions::category_summary`. Amounts are positive, in
+/// minor units of the currency.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CategorySummary {
+    /// The category summarised.
+    pub category: Category,
+    /// The number of debits in the category.
+    pub count: usize,
+    /// The sum of the debits.
+    pub total_minor: i64,
+    /// The average debit, rounded down.
+    pub average_minor: i64,
+}
+
+/// Parameters for listing transactions with `Client::query_transactions`.
+#[derive(Clone, Debug)]
+pub struct TransactionsQuery {
+    /// The account to list the transactions of.
+    pub account_id: AccountId,
+    /// The maximum number of transactions to return.
+    pub limit: Option<u16>,
+    /// Only return transactions after this timestamp or transaction.
+    pub since: Option<Since>,
+    /// Only return transactions created before this timestamp.
+    pub before: Option<DateTime<Utc>>,
+    /// Return the full merchant for each transaction instead of just its id.
+    pub expand_merchant: bool,
+}
+
+impl TransactionsQuery {
+    /// Creates a query for all transactions of an account.
+    pub fn new(account_id: AccountId) -> TransactionsQuery {
+        TransactionsQuery {
+            account_id,
+            limit: None,
+            since: None,
+            before: None,
+            expand_merchant: false,
+        }
+    }
+
+    /// Sets the maximum number of transactions to return.
+    pub fn limit(mut self, limit: u16) -> TransactionsQuery {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Only returns transactions after `since`.
+    pub fn since(mut self, since: Since) -> TransactionsQuery {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only returns transactions created before `before`.
+    pub fn before(mut self, before: DateTime<Utc>) -> TransactionsQuery {
+        self.before = Some(before);
+        self
+    }
+
+    /// Returns the full merchant for each transaction instead of just its id.
+    pub fn expand_merchant(mut self) -> TransactionsQuery {
+        self.expand_merchant = true;
+        self
+    }
+}
+
 /// Response to the transaction future if successful.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct TransactionResponse {
     /// A single transaction.
     pub transaction: Transaction,
 }
 
 /// Describes a pot.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
 pub struct Pot {
     /// Id of the pot
-    pub id: String,
+    pub id: PotId,
     /// User given name.
     pub name: String,
     /// Chosen style.
@@ -213,22 +1215,159 @@ pub struct Pot {
     /// The ISO 4217 currency code.
     pub currency: Currency,
     /// The timestamp in when the pot was created.
+    #[serde(deserialize_with = "rfc3339_or_epoch")]
     pub created: DateTime<Utc>,
     /// The timestamp in when the pot was last updated.
+    #[serde(deserialize_with = "rfc3339_or_epoch")]
     pub updated: DateTime<Utc>,
     /// If the pot has been deleted.
     pub deleted: bool,
+    /// The amount the user is saving up to in minor units of currency, if they set a goal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goal_amount: Option<i64>,
 }
 
-/// Response to the pots future if successful.
-#[derive(Debug, Deserialize)]
-pub struct PotsResponse {
+impl Pot {
+    /// Whether money in the given ISO 4217 currency can be moved into this pot. Depositing from an
+    /// account in another currency is rejected by Monzo.
+    pub fn accepts_currency(&self, currency: &str) -> bool {
+        self.currency.as_str() == currency
+    }
+
+    /// Whether the pot is still in use. Deleted pots are still returned when listing pots.
+    pub fn is_active(&self) -> bool {
+        !self.deleted
+    }
+
+    /// The amount of money in the pot together with its currency.
+    pub fn balance_money(&self) -> Money {
+        Money::new(self.balance, self.currency.clone())
+    }
+}
+
+/// Response to the pots future if successful.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct PotsResponse {
     /// List of pots for the currently authorised user.
     pub pots: Vec<Pot>,
 }
 
-/// Response to the futures in case of an error.
-#[derive(Debug, Deserialize)]
+/// The kind of item to create in the feed with `Client::create_feed_item`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedItemType {
+    /// A title and image, with an optional body. The only type Monzo documents.
+    Basic,
+}
+
+impl FeedItemType {
+    /// The name Monzo uses for the type, for example "basic".
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            FeedItemType::Basic => "basic",
+        }
+    }
+}
+
+/// Optional styling of a feed item, see `FeedItemParams`. Colours are hex strings like `#FCF1EE`.
+#[derive(Clone, Debug, Default)]
+pub struct FeedItemStyle {
+    /// The background colour of the item.
+    pub background_color: Option<String>,
+    /// The colour of the title.
+    pub title_color: Option<String>,
+    /// The colour of the body.
+    pub body_color: Option<String>,
+    /// The url opened when the user taps the item.
+    pub url: Option<String>,
+}
+
+/// What a feed item shows, see `Client::create_feed_item`.
+#[derive(Clone, Debug)]
+pub struct FeedItemParams {
+    /// The title of the item.
+    pub title: String,
+    /// The url of the image shown next to the title.
+    pub image_url: String,
+    /// Text shown below the title.
+    pub body: Option<String>,
+    /// Colours and link of the item.
+    pub style: FeedItemStyle,
+}
+
+impl FeedItemParams {
+    /// Creates the params for an item with only a title and an image, in the default style.
+    pub fn new(title: &str, image_url: &str) -> FeedItemParams {
+        FeedItemParams {
+            title: title.into(),
+            image_url: image_url.into(),
+            body: None,
+            style: FeedItemStyle::default(),
+        }
+    }
+}
+
+/// Describes a webhook, which makes Monzo call the url for every transaction that is created on
+/// the account.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Webhook {
+    /// Id of the webhook.
+    pub id: WebhookId,
+    /// The account the webhook is registered for.
+    pub account_id: AccountId,
+    /// The url Monzo calls.
+    pub url: String,
+}
+
+/// Response to the webhooks future if successful.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Webhooks {
+    /// List of webhooks registered for the account.
+    pub webhooks: Vec<Webhook>,
+}
+
+// The register webhook response wraps the webhook in an object.
+#[derive(Deserialize)]
+struct WebhookResponse {
+    webhook: Webhook,
+}
+
+/// Describes a file, like a receipt image, attached to a transaction.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Attachment {
+    /// Id of the attachment.
+    pub id: AttachmentId,
+    /// The user the attachment belongs to.
+    pub user_id: String,
+    /// The transaction the file is attached to.
+    pub external_id: TransactionId,
+    /// Where the file is hosted.
+    pub file_url: String,
+    /// The mime type of the file, eg. `image/png`.
+    pub file_type: String,
+    /// The timestamp when the attachment was registered.
+    #[serde(deserialize_with = "rfc3339_or_epoch")]
+    pub created: DateTime<Utc>,
+}
+
+/// Response to the request_attachment_upload future if successful.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct AttachmentUpload {
+    /// Where the file will be hosted, to pass to `register_attachment`.
+    pub file_url: String,
+    /// Temporary url to upload the file to with a PUT request.
+    pub upload_url: String,
+}
+
+// The register attachment response wraps the attachment in an object.
+#[derive(Deserialize)]
+struct AttachmentResponse {
+    attachment: Attachment,
+}
+
+/// Response to the futures in case of an error. All fields are empty when the response had no
+/// details Monzo understands, like an HTML page from a gateway.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
 pub struct Error {
     /// The HTTP response code.
     pub code: Option<String>,
@@ -243,165 +1382,1581 @@ pub struct Error {
 }
 
 /// Errors for this crate using `error_chain`.
+#[allow(unexpected_cfgs)]
 pub mod errors {
     error_chain! {
         errors {
             #[doc = "When the Monzo API returns an error response code with more detailed \
-            information."]
-            BadResponse(statuscode: ::StatusCode, error: ::Error)
+            information. The details are boxed to keep `Result`s small."]
+            BadResponse(statuscode: crate::StatusCode, error: Box<crate::Error>) {}
+            #[doc = "When the body of a response can't be parsed. Holds the body and the parse \
+            error."]
+            UnexpectedBody(body: String, error: ::serde_json::Error) {
+                description("unexpected response body")
+                display("unexpected response body '{}': {}", body, error)
+            }
+            #[doc = "When no account matches the given description."]
+            AccountNotFound(description: String) {
+                description("no account matches the description")
+                display("no account matches the description '{}'", description)
+            }
+            #[doc = "When an amount of money to move is not positive."]
+            InvalidAmount(amount: i64) {
+                description("amount must be positive")
+                display("amount must be positive, got {}", amount)
+            }
+            #[doc = "When notes are longer than `MAX_NOTES_LENGTH` bytes."]
+            NotesTooLong(length: usize) {
+                description("notes are too long")
+                display("notes are {} bytes, at most {} are allowed", length, crate::MAX_NOTES_LENGTH)
+            }
+            #[doc = "When creating a pot without a name."]
+            EmptyPotName {
+                description("the pot name is empty")
+                display("the pot name is empty")
+            }
+            #[doc = "When the Monzo API rate limits the client. Holds the number of seconds to \
+            wait before retrying, if the response said."]
+            RateLimited(retry_after: Option<u64>) {
+                description("rate limited by the Monzo API")
+                display("rate limited by the Monzo API{}", match retry_after {
+                    Some(seconds) => format!(", retry after {} seconds", seconds),
+                    None => String::new(),
+                })
+            }
+            #[doc = "When the access token has expired. Refreshing it and retrying the request \
+            should succeed."]
+            TokenExpired {
+                description("the access token has expired")
+                display("the access token has expired")
+            }
+            #[doc = "When a request takes longer than the timeout set on the client."]
+            Timeout {
+                description("request timed out")
+                display("request timed out")
+            }
+            #[doc = "When calling a method that needs a default account while none is set."]
+            NoDefaultAccount {
+                description("no default account is set")
+                display("no default account is set")
+            }
+            #[doc = "When some of a batch of requests failed. Holds the index in the batch, the \
+            account and the error of each failed request."]
+            PartialFailure(failures: Vec<(usize, crate::AccountId, Error)>) {
+                description("some of the requests failed")
+                display("{} of the requests failed: {}", failures.len(), failures
+                    .iter()
+                    .map(|(index, account_id, error)| {
+                        format!("{} ({}): {}", index, account_id, error)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "))
+            }
         }
         foreign_links {
             BadJsonResponse(::serde_json::Error)
             #[doc = "When the Monzo API returns invalid or unexpected json content."];
             NetworkError(::hyper::Error) #[doc = "Returned on network failure."];
+            InvalidUrl(::url::ParseError) #[doc = "When a base url can't be parsed."];
+        }
+    }
+
+    impl Error {
+        /// The HTTP status code of the response that caused the error, or None when the error
+        /// didn't come from a response, like a network error.
+        pub fn status_code(&self) -> Option<crate::StatusCode> {
+            match *self.kind() {
+                ErrorKind::BadResponse(status, _) => Some(status),
+                ErrorKind::RateLimited(_) => Some(crate::StatusCode::TOO_MANY_REQUESTS),
+                ErrorKind::TokenExpired => Some(crate::StatusCode::UNAUTHORIZED),
+                _ => None,
+            }
         }
     }
 }
 
-/// The main interface for this crate.
+/// A response together with how long it took to arrive.
 #[derive(Debug)]
-pub struct Client {
-    client: hyper::client::Client<hyper_tls::HttpsConnector<hyper::client::HttpConnector>>,
+pub struct Timed<T> {
+    /// The response.
+    pub value: T,
+    /// Wall-clock time from sending the request until the response body was fully read.
+    pub duration: Duration,
+}
+
+/// Measures how long a future from the `Client` takes to resolve. The clock starts when the
+/// future is first polled, which is when the request is sent, not when it was created.
+///
+/// ```rust,no_run
+/// let runtime = tokio::runtime::Runtime::new().unwrap();
+/// let monzo = monzo::Client::new("<access_token>");
+/// let response = runtime
+///     .block_on(monzo::timed(monzo.balance("<account_id>".into())))
+///     .unwrap();
+/// println!("Balance {} took {:?}", response.value.balance, response.duration);
+/// ```
+pub async fn timed<F, T, E>(future: F) -> Result<Timed<T>, E>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    let start = Instant::now();
+    let value = future.await?;
+    Ok(Timed {
+        value,
+        duration: start.elapsed(),
+    })
+}
+
+// The number of seconds to wait from a Retry-After header, if it has any. The header holds either
+// the seconds or an HTTP date to wait until; a date in the past means no wait.
+fn retry_after(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(seconds);
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&Utc) - Utc::now();
+    // Round up, so the wait doesn't end just before the date.
+    Some(((wait.num_milliseconds() + 999) / 1000).max(0) as u64)
+}
+
+// The header Monzo uses to recognise a write it has already performed.
+const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+/// Configures a `Client`. Options that aren't set keep the defaults of `Client::new`.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// let monzo = monzo::ClientBuilder::new()
+///     .access_token("<access_token>")
+///     .timeout(Duration::from_secs(10))
+///     .max_retries(3)
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct ClientBuilder {
     access_token: String,
     base_url: Url,
+    timeout: Option<Duration>,
+    max_retries: u8,
+    max_idle_connections: Option<usize>,
+    concurrency_limit: Option<usize>,
+    timer: Option<tokio::runtime::Handle>,
+}
+
+impl ClientBuilder {
+    /// Creates a builder for a client talking to the Monzo API without an access token, no
+    /// timeout and no retries.
+    pub fn new() -> ClientBuilder {
+        ClientBuilder {
+            access_token: String::new(),
+            base_url: "https://api.monzo.com".parse().unwrap(),
+            timeout: None,
+            max_retries: 0,
+            max_idle_connections: None,
+            concurrency_limit: None,
+            timer: None,
+        }
+    }
+
+    /// Sets the access token used for requests.
+    pub fn access_token(mut self, access_token: &str) -> ClientBuilder {
+        self.access_token = access_token.into();
+        self
+    }
+
+    /// Sets the url the Monzo API is reached at. Useful for tests.
+    pub fn base_url(mut self, base_url: Url) -> ClientBuilder {
+        self.base_url = base_url;
+        self
+    }
+
+    /// See `Client::set_timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// See `Client::set_max_retries`.
+    pub fn max_retries(mut self, max_retries: u8) -> ClientBuilder {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets how many idle connections to the Monzo API are kept open for reuse. By default there
+    /// is no limit. Zero closes every connection after its request, which suits short-lived tools.
+    pub fn max_idle_connections(mut self, max_idle_connections: usize) -> ClientBuilder {
+        self.max_idle_connections = Some(max_idle_connections);
+        self
+    }
+
+    /// See `Client::set_concurrency_limit`.
+    pub fn concurrency_limit(mut self, concurrency_limit: usize) -> ClientBuilder {
+        self.concurrency_limit = Some(concurrency_limit);
+        self
+    }
+
+    /// Sets the tokio runtime whose timers drive the timeout and the waits between retries. By
+    /// default the runtime the request runs on is used, which then needs its time driver enabled.
+    pub fn timer(mut self, handle: tokio::runtime::Handle) -> ClientBuilder {
+        self.timer = Some(handle);
+        self
+    }
+
+    /// Creates the client.
+    pub fn build(self) -> Client {
+        let mut builder = hyper::Client::builder();
+        if let Some(max_idle_connections) = self.max_idle_connections {
+            builder.pool_max_idle_per_host(max_idle_connections);
+        }
+        let client = builder.build::<_, Body>(HttpsConnector::new());
+        let mut client = Client::with_http_client(client, &self.access_token, self.base_url);
+        client.max_retries = self.max_retries;
+        client.timeout = self.timeout;
+        client.concurrency_limit = self.concurrency_limit;
+        client.timer = self.timer;
+        client
+    }
+}
+
+// Leaves out the access token, so debug logs don't leak it.
+impl fmt::Debug for ClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ClientBuilder")
+            .field("access_token", &REDACTED)
+            .field("base_url", &self.base_url)
+            .field("timeout", &self.timeout)
+            .field("max_retries", &self.max_retries)
+            .field("max_idle_connections", &self.max_idle_connections)
+            .field("concurrency_limit", &self.concurrency_limit)
+            .field("timer", &self.timer)
+            .finish()
+    }
+}
+
+impl Default for ClientBuilder {
+    fn default() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+}
+
+fn bearer(access_token: &str) -> HeaderValue {
+    format!("Bearer {}", access_token).parse().unwrap()
+}
+
+fn copy_request(parts: &request::Parts, body: &Bytes) -> Request<Body> {
+    let mut request = Request::new(Body::from(body.clone()));
+    *request.method_mut() = parts.method.clone();
+    *request.uri_mut() = parts.uri.clone();
+    *request.headers_mut() = parts.headers.clone();
+    request
+}
+
+// The longest the client waits before retrying a request by itself.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(64);
+
+// How long to wait before retrying without a Retry-After header: exponentially longer from one
+// second, but never more than `MAX_RETRY_DELAY`.
+fn backoff(retries: u8) -> Duration {
+    Duration::from_secs(1 << retries.min(6))
+}
+
+// Whether sending the request twice has the same effect as sending it once, so it can be retried
+// when it isn't clear whether Monzo handled it. Other writes are only safe with an idempotency key.
+fn is_idempotent(parts: &request::Parts) -> bool {
+    parts.method == Method::GET
+        || parts.method == Method::PUT
+        || parts.headers.contains_key(IDEMPOTENCY_KEY)
+}
+
+// Whether a network error is likely to go away when trying again, like a connection that was reset
+// or closed before the response came in. Errors like an unknown host or a bad certificate are not.
+fn is_transient(error: &hyper::Error) -> bool {
+    if error.is_incomplete_message() {
+        return true;
+    }
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<io::Error>() {
+            return matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+            );
+        }
+        source = e.source();
+    }
+    false
+}
+
+// Turns responses with an error status into errors.
+async fn check_status(response: Response<Body>) -> Result<Response<Body>, errors::Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = retry_after(response.headers());
+        return Err(errors::ErrorKind::RateLimited(retry_after).into());
+    }
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    match serde_json::from_slice::<Error>(&body) {
+        // Other 401s, like a revoked token, can't be fixed by refreshing.
+        Ok(ref error)
+            if status == StatusCode::UNAUTHORIZED
+                && error.error.as_deref() == Some("invalid_token") =>
+        {
+            Err(errors::ErrorKind::TokenExpired.into())
+        }
+        Ok(error) => Err(errors::ErrorKind::BadResponse(status, Box::new(error)).into()),
+        // The status is what matters here, the body doesn't come from Monzo.
+        Err(_) => Err(errors::ErrorKind::BadResponse(status, Box::default()).into()),
+    }
+}
+
+// Reads the body of the response and passes it to the handler.
+async fn handle_body<T, F>(
+    response: Response<Body>,
+    response_handler: F,
+) -> Result<T, errors::Error>
+where
+    F: FnOnce(Bytes) -> Result<T, errors::Error>,
+{
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    match response_handler(body.clone()) {
+        Err(errors::Error(errors::ErrorKind::BadJsonResponse(error), _)) => {
+            Err(unexpected_body(&body, error))
+        }
+        result => result,
+    }
+}
+
+// Keeps the body around when it can't be parsed, to see what Monzo sent.
+fn unexpected_body(body: &[u8], error: serde_json::Error) -> errors::Error {
+    let body = String::from_utf8_lossy(body).into_owned();
+    errors::ErrorKind::UnexpectedBody(body, error).into()
+}
+
+// Finds the transactions in a `{"transactions": [...]}` body as it arrives, so each can be parsed
+// without buffering the whole body.
+#[derive(Default)]
+struct TransactionScanner {
+    buffer: Vec<u8>,
+    // How far the buffer has been scanned.
+    position: usize,
+    // Nesting of objects and arrays at the position, the envelope included.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    in_array: bool,
+    done: bool,
+    item_start: usize,
+}
+
+impl TransactionScanner {
+    fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    // Returns the json of the next transaction, if it has fully arrived.
+    fn next_item(&mut self) -> Option<Vec<u8>> {
+        while self.position < self.buffer.len() && !self.done {
+            let byte = self.buffer[self.position];
+            self.position += 1;
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => {
+                    if self.in_array && self.depth == 2 {
+                        self.item_start = self.position - 1;
+                    } else if byte == b'[' && self.depth == 1 {
+                        self.in_array = true;
+                    }
+                    self.depth += 1;
+                }
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.in_array && self.depth == 2 {
+                        let item = self.buffer[self.item_start..self.position].to_vec();
+                        self.buffer.drain(..self.position);
+                        self.position = 0;
+                        return Some(item);
+                    }
+                    if self.in_array && self.depth == 1 {
+                        self.done = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Drop what has been scanned, except for a transaction that is still arriving.
+        if self.in_array {
+            let keep = if self.depth > 2 {
+                self.item_start
+            } else {
+                self.position
+            };
+            self.buffer.drain(..keep);
+            self.position -= keep;
+            self.item_start = 0;
+        }
+        None
+    }
+
+    // Whether the end of the list of transactions was found.
+    fn is_done(&self) -> bool {
+        self.done
+    }
+
+    // The error for a body that ended before the list of transactions did.
+    fn error(&self) -> Option<errors::Error> {
+        serde_json::from_slice::<Transactions>(&self.buffer)
+            .err()
+            .map(|e| unexpected_body(&self.buffer, e))
+    }
+}
+
+// The progress of a transactions stream.
+enum TransactionStreamState {
+    Request(Request<Body>),
+    Body(Body, TransactionScanner),
+    Done,
+}
+
+// The progress of an all transactions stream.
+struct TransactionPages {
+    // Where the next page starts.
+    since: Option<Since>,
+    page: std::vec::IntoIter<Transaction>,
+    done: bool,
 }
 
 /// The main interface for this crate.
-impl Client {
-    // The account_id param on requests.
-    const ACCOUNT_ID: &'static str = "account_id";
+#[derive(Clone)]
+pub struct Client<C = HttpsConnector<HttpConnector>> {
+    client: hyper::Client<C>,
+    // Shared with clones, so a refresh by one of them is seen by all.
+    access_token: Arc<RwLock<String>>,
+    base_url: Url,
+    default_account: Option<AccountId>,
+    max_retries: u8,
+    timeout: Option<Duration>,
+    auto_refresh: Option<Arc<AutoRefresh>>,
+    concurrency_limit: Option<usize>,
+    timer: Option<tokio::runtime::Handle>,
+}
+
+// What's needed to refresh the access token when it expires. The lock makes concurrent requests
+// wait for a single refresh.
+struct AutoRefresh {
+    client_id: String,
+    client_secret: String,
+    refresh_token: tokio::sync::Mutex<String>,
+}
+
+// Shown in place of credentials when debug printing the client.
+const REDACTED: &str = "<redacted>";
+
+// Leaves out the access token and the refresh credentials, so debug logs don't leak them.
+impl<C> fmt::Debug for Client<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("client", &self.client)
+            .field("access_token", &REDACTED)
+            .field("base_url", &self.base_url)
+            .field("default_account", &self.default_account)
+            .field("max_retries", &self.max_retries)
+            .field("timeout", &self.timeout)
+            .field("auto_refresh", &self.auto_refresh)
+            .field("concurrency_limit", &self.concurrency_limit)
+            .field("timer", &self.timer)
+            .finish()
+    }
+}
+
+impl fmt::Debug for AutoRefresh {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AutoRefresh")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &REDACTED)
+            .field("refresh_token", &REDACTED)
+            .finish()
+    }
+}
 
+/// The main interface for this crate.
+impl Client {
     /// Creates a new Monzo client.
     pub fn new(access_token: &str) -> Client {
-        Client::new_with_base_url(access_token, "https://api.monzo.com".parse().unwrap())
+        ClientBuilder::new().access_token(access_token).build()
     }
 
     /// Creates a new Monzo client with another base url. Useful for tests.
     pub fn new_with_base_url(access_token: &str, base_url: Url) -> Client {
+        ClientBuilder::new()
+            .access_token(access_token)
+            .base_url(base_url)
+            .build()
+    }
+
+    /// Returns a builder to configure a client with.
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// Creates a new Monzo client with a `hyper::Uri` as base url, for callers already using
+    /// hyper types.
+    pub fn new_with_uri(access_token: &str, base_uri: Uri) -> Result<Client, errors::Error> {
+        let base_url = Url::parse(&base_uri.to_string())?;
+        Ok(Client::new_with_base_url(access_token, base_url))
+    }
+}
+
+impl<C> Client<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    // The account_id param on requests.
+    const ACCOUNT_ID: &'static str = "account_id";
+    // The number of transactions to fetch per page, the most Monzo allows.
+    const PAGE_SIZE: u16 = 100;
+
+    /// Creates a new Monzo client that sends its requests through an existing hyper client, for
+    /// example one with a proxy connector or a mock transport.
+    pub fn with_http_client(
+        client: hyper::Client<C>,
+        access_token: &str,
+        base_url: Url,
+    ) -> Client<C> {
         Client {
-            client: ::hyper::Client::builder()
-                .build::<_, ::hyper::Body>(::hyper_tls::HttpsConnector::new(1).unwrap()),
-            access_token: access_token.into(),
-            base_url: base_url,
+            client,
+            access_token: Arc::new(RwLock::new(access_token.into())),
+            base_url,
+            default_account: None,
+            max_retries: 0,
+            timeout: None,
+            auto_refresh: None,
+            concurrency_limit: None,
+            timer: None,
+        }
+    }
+
+    /// Sets the account used by the `_default` variants of the methods, for apps that only ever
+    /// touch one account.
+    pub fn with_default_account(mut self, account_id: AccountId) -> Client<C> {
+        self.default_account = Some(account_id);
+        self
+    }
+
+    /// Makes the client refresh the access token when Monzo says it expired, and send the request
+    /// once more with the new token. The refresh token Monzo hands out with the new access token
+    /// is kept for the next refresh.
+    pub fn with_auto_refresh(
+        mut self,
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: &str,
+    ) -> Client<C> {
+        self.auto_refresh = Some(Arc::new(AutoRefresh {
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            refresh_token: tokio::sync::Mutex::new(refresh_token.into()),
+        }));
+        self
+    }
+
+    /// Replaces the access token used for requests, for example after a refresh. Clones of the
+    /// client use the new token too.
+    pub fn set_access_token(&self, access_token: String) {
+        *self.access_token.write().unwrap() = access_token;
+    }
+
+    fn access_token(&self) -> String {
+        self.access_token.read().unwrap().clone()
+    }
+
+    /// Retries requests that were rate limited, failed with a server error or lost their
+    /// connection up to `max_retries` times, waiting as long as the `Retry-After` header asks.
+    /// Requests that are asked to wait more than about a minute fail right away instead.
+    /// Writes other than PUT are only retried after a server error or lost connection when they
+    /// carry an idempotency key, as Monzo may have handled them already. Off by default.
+    pub fn set_max_retries(&mut self, max_retries: u8) {
+        self.max_retries = max_retries;
+    }
+
+    /// Fails requests with `errors::ErrorKind::Timeout` when they take longer than the timeout,
+    /// retries included. By default requests wait forever.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    /// Limits how many requests methods like `balances` have in flight at once, to stay clear of
+    /// Monzo's rate limits. By default they send all their requests at once.
+    pub fn set_concurrency_limit(&mut self, concurrency_limit: usize) {
+        self.concurrency_limit = Some(concurrency_limit);
+    }
+
+    fn default_account(&self) -> Result<AccountId, errors::Error> {
+        self.default_account
+            .clone()
+            .ok_or_else(|| errors::ErrorKind::NoDefaultAccount.into())
+    }
+
+    // Appends the path segments and query params to the base url. The base url may already have a
+    // path, for example when Monzo is mounted under a prefix by a gateway.
+    fn build_uri(&self, segments: &[&str], params: &[(&str, &str)]) -> Uri {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .unwrap()
+            .pop_if_empty()
+            .extend(segments);
+        if !params.is_empty() {
+            url.query_pairs_mut().extend_pairs(params);
         }
+        url.into_string().parse().unwrap()
     }
 
     fn create_request(&self, uri: Uri) -> Request<Body> {
-        Request::builder()
-            .method("GET")
+        self.create_form_request(Method::GET, uri, &[])
+    }
+
+    // Url-encodes the params into the body, unless there are none.
+    fn create_form_request(
+        &self,
+        method: Method,
+        uri: Uri,
+        params: &[(&str, &str)],
+    ) -> Request<Body> {
+        let mut request = self.create_unauthenticated_form_request(method, uri, params);
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, bearer(&self.access_token()));
+        request
+    }
+
+    // Like create_form_request, with an Idempotency-Key header so Monzo performs the write only
+    // once, however often the request is sent.
+    fn create_idempotent_form_request(
+        &self,
+        method: Method,
+        uri: Uri,
+        params: &[(&str, &str)],
+        idempotency_key: &DedupeId,
+    ) -> Request<Body> {
+        let mut request = self.create_form_request(method, uri, params);
+        request.headers_mut().insert(
+            IDEMPOTENCY_KEY,
+            HeaderValue::from_str(idempotency_key.as_str()).unwrap(),
+        );
+        request
+    }
+
+    // Some endpoints, like ping, don't need an access token.
+    fn create_unauthenticated_request(&self, uri: Uri) -> Request<Body> {
+        self.create_unauthenticated_form_request(Method::GET, uri, &[])
+    }
+
+    fn create_unauthenticated_form_request(
+        &self,
+        method: Method,
+        uri: Uri,
+        params: &[(&str, &str)],
+    ) -> Request<Body> {
+        let builder = Request::builder()
+            .method(method)
             .uri(uri)
-            .header(
-                AUTHORIZATION,
-                format!("Bearer {}", self.access_token.as_str()).as_str(),
-            )
-            .body(Body::empty())
+            .header(ACCEPT, "application/json");
+        if params.is_empty() {
+            return builder.body(Body::empty()).unwrap();
+        }
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+        builder
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body.into())
             .unwrap()
     }
 
-    fn make_request<T: 'static, F: 'static>(
+    async fn make_request<T, F>(
         &self,
-        uri: Uri,
+        request: Request<Body>,
         response_handler: F,
-    ) -> Box<Future<Item = T, Error = errors::Error>>
+    ) -> Result<T, errors::Error>
     where
-        F: Fn(Chunk) -> Result<T, errors::Error>,
+        F: FnOnce(Bytes) -> Result<T, errors::Error>,
     {
-        let request = self.create_request(uri);
-        let response: hyper::client::ResponseFuture = self.client.request(request);
-        let future = response
-            .map_err(|err: hyper::Error| -> errors::Error { err.into() })
-            .and_then(|res| {
-                let status = res.status();
-                res.into_body()
-                    .concat2()
-                    .map_err(|err: hyper::Error| err.into())
-                    .and_then(move |body: Chunk| {
-                        if !status.is_success() {
-                            let error: Error = serde_json::from_slice(&body)?;
-                            return Err(errors::ErrorKind::BadResponse(status, error).into());
-                        }
-                        response_handler(body)
-                    })
-            });
+        self.with_timeout(async {
+            let response = self.send_checked(request).await?;
+            handle_body(response, response_handler).await
+        }).await
+    }
+
+    async fn with_timeout<T, F>(&self, work: F) -> Result<T, errors::Error>
+    where
+        F: Future<Output = Result<T, errors::Error>>,
+    {
+        match self.timeout {
+            Some(timeout) => match self.on_timer(|| tokio::time::timeout(timeout, work)).await {
+                Ok(result) => result,
+                Err(_) => Err(errors::ErrorKind::Timeout.into()),
+            },
+            None => work.await,
+        }
+    }
+
+    // Creates a timer future on the runtime set with `ClientBuilder::timer`, if any. The timer
+    // stays bound to that runtime after the guard is dropped, so the future can be awaited
+    // anywhere.
+    fn on_timer<T, F: FnOnce() -> T>(&self, create: F) -> T {
+        let _guard = self.timer.as_ref().map(|handle| handle.enter());
+        create()
+    }
+
+    // Sends the request and turns error responses into errors. With auto refresh on, an expired
+    // access token is refreshed and the request sent once more, but not again if that fails too.
+    async fn send_checked(&self, request: Request<Body>) -> Result<Response<Body>, errors::Error> {
+        let auto_refresh = match self.auto_refresh {
+            Some(ref auto_refresh) if request.headers().contains_key(AUTHORIZATION) => auto_refresh,
+            _ => return check_status(self.send_request(request).await?).await,
+        };
+        let (parts, body) = request.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        let expired_token = self.access_token();
+        match check_status(self.send_request(copy_request(&parts, &body)).await?).await {
+            Err(errors::Error(errors::ErrorKind::TokenExpired, _)) => {
+                self.refresh_access_token(auto_refresh, &expired_token).await?;
+                let mut request = copy_request(&parts, &body);
+                request
+                    .headers_mut()
+                    .insert(AUTHORIZATION, bearer(&self.access_token()));
+                check_status(self.send_request(request).await?).await
+            }
+            result => result,
+        }
+    }
+
+    // Replaces the expired access token, unless another request already did.
+    async fn refresh_access_token(
+        &self,
+        auto_refresh: &AutoRefresh,
+        expired_token: &str,
+    ) -> Result<(), errors::Error> {
+        let mut refresh_token = auto_refresh.refresh_token.lock().await;
+        if self.access_token() != expired_token {
+            return Ok(());
+        }
+        let token = self
+            .refresh_token(
+                &auto_refresh.client_id,
+                &auto_refresh.client_secret,
+                &refresh_token,
+            )
+            .await?;
+        if let Some(new_refresh_token) = token.refresh_token {
+            *refresh_token = new_refresh_token;
+        }
+        *self.access_token.write().unwrap() = token.access_token;
+        Ok(())
+    }
 
-        Box::new(future)
+    // Sends the request, retrying it if the client is configured to.
+    async fn send_request(&self, request: Request<Body>) -> Result<Response<Body>, errors::Error> {
+        // The body is kept around so the request can be rebuilt for every attempt.
+        let (parts, body) = request.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        let idempotent = is_idempotent(&parts);
+        let mut retries = 0;
+        loop {
+            let request = copy_request(&parts, &body);
+            let response = match self.client.request(request).await {
+                Ok(response) => response,
+                Err(ref e) if idempotent && is_transient(e) && retries < self.max_retries => {
+                    log::debug!("{} {} -> {}, retrying", parts.method, parts.uri, e);
+                    self.on_timer(|| tokio::time::sleep(backoff(retries))).await;
+                    retries += 1;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let status = response.status();
+            // Only the method and uri are logged, the access token lives in a header.
+            log::debug!("{} {} -> {}", parts.method, parts.uri, status);
+            // Rate limited requests weren't handled, but a server error may come after a write.
+            let retryable = status == StatusCode::TOO_MANY_REQUESTS
+                || (idempotent && status.is_server_error());
+            if !retryable || retries >= self.max_retries {
+                return Ok(response);
+            }
+            let delay = retry_after(response.headers())
+                .map(Duration::from_secs)
+                .unwrap_or_else(|| backoff(retries));
+            // Waiting longer is left to the caller, who sees the delay in the error.
+            if delay > MAX_RETRY_DELAY {
+                return Ok(response);
+            }
+            retries += 1;
+            self.on_timer(|| tokio::time::sleep(delay)).await;
+        }
+    }
+
+    /// Checks that the Monzo API is reachable. Doesn't need a valid access token, so it is useful
+    /// as a cheap liveness check.
+    pub async fn ping(&self) -> Result<(), errors::Error> {
+        let uri = self.build_uri(&["ping"], &[]);
+
+        self.make_request(self.create_unauthenticated_request(uri), |_| Ok(()))
+            .await
+    }
+
+    /// Exchanges the authorization code from the OAuth redirect for an access token. Doesn't need
+    /// an access token, so any client can be used to complete the OAuth flow.
+    pub async fn exchange_code(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        redirect_uri: &str,
+        code: &str,
+    ) -> Result<AccessToken, errors::Error> {
+        self.request_token(&[
+            ("grant_type", "authorization_code"),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("redirect_uri", redirect_uri),
+            ("code", code),
+        ]).await
+    }
+
+    /// Gets a new access token with the refresh token of an expiring one. Use `set_access_token`
+    /// to make the client use the new token.
+    pub async fn refresh_token(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: &str,
+    ) -> Result<AccessToken, errors::Error> {
+        self.request_token(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("refresh_token", refresh_token),
+        ]).await
+    }
+
+    async fn request_token(&self, params: &[(&str, &str)]) -> Result<AccessToken, errors::Error> {
+        let uri = self.build_uri(&["oauth2", "token"], &[]);
+        let request = self.create_unauthenticated_form_request(Method::POST, uri, params);
+
+        // Not sent with make_request, as refreshing an expired token from there ends up here.
+        self.with_timeout(async {
+            let response = check_status(self.send_request(request).await?).await?;
+            handle_body(response, |body| {
+                let t: AccessToken = serde_json::from_slice(&body)?;
+                Ok(t)
+            }).await
+        }).await
+    }
+
+    /// Returns information about the current access token.
+    pub async fn whoami(&self) -> Result<WhoAmI, errors::Error> {
+        let uri = self.build_uri(&["ping", "whoami"], &[]);
+
+        self.make_request(self.create_request(uri), |body| {
+            let w: WhoAmI = serde_json::from_slice(&body)?;
+            Ok(w)
+        }).await
+    }
+
+    /// Checks whether the access token can still be used, for example at startup. A rejected token
+    /// gives false instead of an error, other failures are still returned as errors.
+    pub async fn is_token_valid(&self) -> Result<bool, errors::Error> {
+        match self.whoami().await {
+            Ok(whoami) => Ok(whoami.authenticated),
+            Err(ref e) if e.status_code() == Some(StatusCode::UNAUTHORIZED) => Ok(false),
+            Err(e) => Err(e),
+        }
     }
 
     /// Returns a list of accounts owned by the currently authorised user.
-    pub fn accounts(&self) -> Box<Future<Item = Accounts, Error = errors::Error>> {
-        let mut url = self.base_url.clone();
-        url.path_segments_mut().unwrap().push("accounts");
-        let uri: Uri = url.into_string().parse().unwrap();
+    pub async fn accounts(&self) -> Result<Accounts, errors::Error> {
+        self.accounts_filtered(None).await
+    }
+
+    /// Returns the accounts owned by the currently authorised user, only those of the given type
+    /// if there is one, eg. `uk_retail` to skip closed prepaid accounts.
+    pub async fn accounts_filtered(
+        &self,
+        account_type: Option<String>,
+    ) -> Result<Accounts, errors::Error> {
+        let params: Vec<(&str, &str)> = account_type
+            .iter()
+            .map(|account_type| ("account_type", account_type.as_str()))
+            .collect();
+        let uri = self.build_uri(&["accounts"], &params);
 
-        self.make_request(uri, |body| {
+        self.make_request(self.create_request(uri), |body| {
             let a: Accounts = serde_json::from_slice(&body)?;
             Ok(a)
-        })
+        }).await
     }
 
-    /// Retrieve information about an account’s balance.
-    pub fn balance(
+    /// Returns the accounts owned by the currently authorised user, only those of the given type
+    /// if there is one, and only open ones unless `include_closed` is set. The filters are applied
+    /// here rather than by Monzo, which can't filter on whether an account is closed.
+    pub async fn accounts_matching(
         &self,
-        account_id: AccountId,
-    ) -> Box<Future<Item = Balance, Error = errors::Error>> {
-        let mut url = self.base_url.clone();
-        url.path_segments_mut().unwrap().push("balance");
-        url.query_pairs_mut()
-            .append_pair(Client::ACCOUNT_ID, &account_id);
-        let uri: Uri = url.into_string().parse().unwrap();
+        account_type: Option<String>,
+        include_closed: bool,
+    ) -> Result<Vec<Account>, errors::Error> {
+        let accounts = self.accounts().await?.accounts;
+        Ok(accounts
+            .into_iter()
+            .filter(|a| account_type.is_none() || a.account_type == account_type)
+            .filter(|a| include_closed || !a.closed)
+            .collect())
+    }
+
+    /// Retrieve information about an account’s balance.
+    pub async fn balance(&self, account_id: AccountId) -> Result<Balance, errors::Error> {
+        let uri = self.build_uri(&["balance"], &[(Self::ACCOUNT_ID, &account_id)]);
 
-        self.make_request(uri, |body| {
+        self.make_request(self.create_request(uri), |body| {
             let b: Balance = serde_json::from_slice(&body)?;
             Ok(b)
-        })
+        }).await
+    }
+
+    /// Retrieve the balances of several accounts at once, requesting them concurrently up to the
+    /// concurrency limit. The balances are returned in the order of `account_ids`, paired with
+    /// their account. Fails if any of the requests fails.
+    pub async fn balances(
+        &self,
+        account_ids: Vec<AccountId>,
+    ) -> Result<Vec<(AccountId, Balance)>, errors::Error> {
+        let limit = self.concurrency_limit.unwrap_or(account_ids.len()).max(1);
+        let requests = account_ids.iter().map(|id| self.balance(id.clone()));
+        let balances: Vec<_> = stream::iter(requests).buffered(limit).collect().await;
+        account_ids
+            .into_iter()
+            .zip(balances)
+            .map(|(id, balance)| Ok((id, balance?)))
+            .collect()
+    }
+
+    /// Retrieve information about the default account’s balance.
+    pub async fn balance_default(&self) -> Result<Balance, errors::Error> {
+        self.balance(self.default_account()?).await
+    }
+
+    /// Retrieve the balance of the account with the given description, failing with
+    /// `errors::ErrorKind::AccountNotFound` if there is none.
+    pub async fn account_balance(&self, description: &str) -> Result<Balance, errors::Error> {
+        let accounts = self.accounts().await?.accounts;
+        match accounts.into_iter().find(|a| a.description == description) {
+            Some(account) => self.balance(account.id).await,
+            None => Err(errors::ErrorKind::AccountNotFound(description.to_string()).into()),
+        }
     }
 
     /// Returns a list of transactions on the user’s account.
-    pub fn transactions(
+    pub async fn transactions(&self, account_id: AccountId) -> Result<Transactions, errors::Error> {
+        self.query_transactions(TransactionsQuery::new(account_id))
+            .await
+    }
+
+    /// Returns only the settled transactions on the user’s account, leaving out pending card
+    /// payments. Useful for reconciling against a statement.
+    pub async fn transactions_settled_only(
         &self,
         account_id: AccountId,
-    ) -> Box<Future<Item = Transactions, Error = errors::Error>> {
-        let mut url = self.base_url.clone();
-        url.path_segments_mut().unwrap().push("transactions");
-        url.query_pairs_mut()
-            .append_pair(Client::ACCOUNT_ID, &account_id);
-        let uri: Uri = url.into_string().parse().unwrap();
+    ) -> Result<Transactions, errors::Error> {
+        let mut transactions = self.transactions(account_id).await?;
+        transactions.transactions.retain(Transaction::is_settled);
+        Ok(transactions)
+    }
+
+    /// Returns the transactions on the user’s account one by one, parsing each as it arrives
+    /// instead of buffering the whole response, which keeps memory low for long histories. The
+    /// timeout applies to receiving the response headers, not to the whole stream. Pass the last
+    /// transaction seen as `from_cursor` to resume a sync where it stopped.
+    pub fn transactions_stream(
+        &self,
+        account_id: AccountId,
+        from_cursor: Option<Since>,
+    ) -> impl Stream<Item = Result<Transaction, errors::Error>> + '_ {
+        let mut params = vec![(Self::ACCOUNT_ID, account_id)];
+        if let Some(since) = from_cursor {
+            params.push(("since", since.to_param()));
+        }
+        let params: Vec<(&str, &str)> = params.iter().map(|&(k, ref v)| (k, v.as_str())).collect();
+        let uri = self.build_uri(&["transactions"], &params);
+        let request = self.create_request(uri);
 
-        self.make_request(uri, |body| {
+        stream::unfold(
+            TransactionStreamState::Request(request),
+            move |mut state| async move {
+                loop {
+                    state = match state {
+                        TransactionStreamState::Request(request) => {
+                            let response = self
+                                .with_timeout(self.send_checked(request))
+                                .await;
+                            match response {
+                                Ok(response) => TransactionStreamState::Body(
+                                    response.into_body(),
+                                    TransactionScanner::default(),
+                                ),
+                                Err(e) => return Some((Err(e), TransactionStreamState::Done)),
+                            }
+                        }
+                        TransactionStreamState::Body(mut body, mut scanner) => {
+                            if let Some(item) = scanner.next_item() {
+                                let transaction = serde_json::from_slice(&item)
+                                    .map_err(|e| unexpected_body(&item, e));
+                                return Some((
+                                    transaction,
+                                    TransactionStreamState::Body(body, scanner),
+                                ));
+                            }
+                            if scanner.is_done() {
+                                return None;
+                            }
+                            match body.data().await {
+                                Some(Ok(chunk)) => {
+                                    scanner.push(&chunk);
+                                    TransactionStreamState::Body(body, scanner)
+                                }
+                                Some(Err(e)) => {
+                                    return Some((Err(e.into()), TransactionStreamState::Done))
+                                }
+                                None => {
+                                    return scanner
+                                        .error()
+                                        .map(|e| (Err(e), TransactionStreamState::Done))
+                                }
+                            }
+                        }
+                        TransactionStreamState::Done => return None,
+                    }
+                }
+            },
+        )
+    }
+
+    /// Returns the full history of the user’s account, oldest first, fetching it page by page. Each
+    /// page starts after the last transaction of the previous one, until a page comes back empty.
+    pub fn all_transactions(
+        &self,
+        account_id: AccountId,
+    ) -> impl Stream<Item = Result<Transaction, errors::Error>> + '_ {
+        let pages = TransactionPages {
+            since: None,
+            page: Vec::new().into_iter(),
+            done: false,
+        };
+
+        stream::unfold(pages, move |mut pages| {
+            let account_id = account_id.clone();
+            async move {
+                loop {
+                    if let Some(transaction) = pages.page.next() {
+                        return Some((Ok(transaction), pages));
+                    }
+                    if pages.done {
+                        return None;
+                    }
+                    let query = TransactionsQuery {
+                        account_id: account_id.clone(),
+                        limit: Some(Self::PAGE_SIZE),
+                        since: pages.since.take(),
+                        before: None,
+                        expand_merchant: false,
+                    };
+                    match self.query_transactions(query).await {
+                        Ok(page) => {
+                            match page.transactions.last() {
+                                Some(last) => pages.since = Some(Since::Cursor(last.id.clone())),
+                                None => return None,
+                            }
+                            pages.page = page.transactions.into_iter();
+                        }
+                        Err(e) => {
+                            pages.done = true;
+                            return Some((Err(e), pages));
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Returns a list of transactions on the user’s account, filtered and expanded as described by
+    /// the query.
+    pub async fn query_transactions(
+        &self,
+        query: TransactionsQuery,
+    ) -> Result<Transactions, errors::Error> {
+        let mut params = vec![(Self::ACCOUNT_ID, query.account_id)];
+        if let Some(limit) = query.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if let Some(since) = query.since {
+            params.push(("since", since.to_param()));
+        }
+        if let Some(before) = query.before {
+            params.push(("before", before.to_rfc3339_opts(SecondsFormat::Secs, true)));
+        }
+        if query.expand_merchant {
+            params.push(("expand[]", "merchant".to_string()));
+        }
+        let params: Vec<(&str, &str)> = params.iter().map(|&(k, ref v)| (k, v.as_str())).collect();
+        let uri = self.build_uri(&["transactions"], &params);
+
+        self.make_request(self.create_request(uri), |body| {
             let t: Transactions = serde_json::from_slice(&body)?;
             Ok(t)
-        })
+        }).await
+    }
+
+    /// Returns a page of transactions on the user’s account. At most `limit` transactions after
+    /// `since` and created before `before` are returned.
+    pub async fn transactions_paginated(
+        &self,
+        account_id: AccountId,
+        limit: Option<u16>,
+        since: Option<Since>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<Transactions, errors::Error> {
+        self.query_transactions(TransactionsQuery {
+            account_id,
+            limit,
+            since,
+            before,
+            expand_merchant: false,
+        }).await
+    }
+
+    /// Returns at most `limit` transactions created after the transaction with the given id. Pass
+    /// the id of the newest transaction seen so far to poll for new ones.
+    pub async fn transactions_since_id(
+        &self,
+        account_id: AccountId,
+        since_transaction_id: TransactionId,
+        limit: Option<u16>,
+    ) -> Result<Transactions, errors::Error> {
+        self.transactions_paginated(
+            account_id,
+            limit,
+            Some(Since::Cursor(since_transaction_id)),
+            None,
+        ).await
+    }
+
+    /// Returns a list of transactions on the default account.
+    pub async fn transactions_default(&self) -> Result<Transactions, errors::Error> {
+        self.transactions(self.default_account()?).await
     }
 
     /// Returns a list of transactions on the user’s account.
-    pub fn transaction(
+    pub async fn transaction(
         &self,
         account_id: AccountId,
         transaction_id: TransactionId,
-    ) -> Box<Future<Item = TransactionResponse, Error = errors::Error>> {
-        let mut url = self.base_url.clone();
-        url.path_segments_mut().unwrap().push("transactions");
-        url.path_segments_mut().unwrap().push(&transaction_id);
-        url.query_pairs_mut()
-            .append_pair(Client::ACCOUNT_ID, &account_id);
-        let uri: Uri = url.into_string().parse().unwrap();
+    ) -> Result<TransactionResponse, errors::Error> {
+        let uri = self.build_uri(
+            &["transactions", &transaction_id],
+            &[(Self::ACCOUNT_ID, &account_id)],
+        );
 
-        self.make_request(uri, |body| {
+        self.make_request(self.create_request(uri), |body| {
             let t: TransactionResponse = serde_json::from_slice(&body)?;
             Ok(t)
-        })
+        }).await
     }
 
     /// Returns a list of pots in the user’s account.
-    pub fn pots(&self) -> Box<Future<Item = PotsResponse, Error = errors::Error>> {
-        let mut url = self.base_url.clone();
-        url.path_segments_mut().unwrap().push("pots");
-        url.path_segments_mut().unwrap().push("listV1");
-        let uri: Uri = url.into_string().parse().unwrap();
+    pub async fn pots(&self) -> Result<PotsResponse, errors::Error> {
+        let uri = self.build_uri(&["pots", "listV1"], &[]);
 
-        self.make_request(uri, |body| {
+        self.make_request(self.create_request(uri), |body| {
             let t: PotsResponse = serde_json::from_slice(&body)?;
             Ok(t)
-        })
+        }).await
+    }
+
+    /// Returns the pots belonging to the account, leaving out pots of the user's other accounts,
+    /// like those of a joint account.
+    pub async fn pots_for_account(
+        &self,
+        account_id: AccountId,
+    ) -> Result<PotsResponse, errors::Error> {
+        let uri = self.build_uri(&["pots"], &[("current_account_id", &account_id)]);
+
+        self.make_request(self.create_request(uri), |body| {
+            let t: PotsResponse = serde_json::from_slice(&body)?;
+            Ok(t)
+        }).await
+    }
+
+    /// Returns the pots belonging to the account that haven't been deleted.
+    pub async fn active_pots(&self, account_id: AccountId) -> Result<PotsResponse, errors::Error> {
+        let mut pots = self.pots_for_account(account_id).await?;
+        pots.pots.retain(Pot::is_active);
+        Ok(pots)
+    }
+
+    /// Creates a pot for the account, optionally with the amount the user is saving up to.
+    /// Returns the new pot.
+    pub async fn create_pot(
+        &self,
+        account_id: AccountId,
+        name: String,
+        goal_amount: Option<i64>,
+    ) -> Result<Pot, errors::Error> {
+        if name.trim().is_empty() {
+            return Err(errors::ErrorKind::EmptyPotName.into());
+        }
+        let uri = self.build_uri(&["pots"], &[]);
+        let goal_amount = goal_amount.map(|amount| amount.to_string());
+        let mut params = vec![("pot_current_account_id", account_id.as_str()), ("name", &name)];
+        if let Some(ref goal_amount) = goal_amount {
+            params.push(("goal_amount", goal_amount));
+        }
+        let request = self.create_form_request(Method::POST, uri, &params);
+
+        self.make_request(request, |body| {
+            let p: Pot = serde_json::from_slice(&body)?;
+            Ok(p)
+        }).await
+    }
+
+    /// Deletes a pot. Returns the pot, now marked deleted, or None when Monzo answers without it.
+    pub async fn delete_pot(&self, pot_id: PotId) -> Result<Option<Pot>, errors::Error> {
+        let uri = self.build_uri(&["pots", &pot_id], &[]);
+        let request = self.create_form_request(Method::DELETE, uri, &[]);
+
+        self.make_request(request, |body| {
+            if body.is_empty() {
+                return Ok(None);
+            }
+            let value: serde_json::Value = serde_json::from_slice(&body)?;
+            if value.as_object().is_some_and(|o| o.is_empty()) {
+                return Ok(None);
+            }
+            let p: Pot = serde_json::from_value(value)?;
+            Ok(Some(p))
+        }).await
+    }
+
+    /// Moves money from an account into a pot. The `dedupe_id` makes retrying safe: Monzo only
+    /// performs the deposit once for the same id. Returns the updated pot.
+    pub async fn deposit_into_pot(
+        &self,
+        pot_id: PotId,
+        source_account_id: AccountId,
+        amount: i64,
+        dedupe_id: DedupeId,
+    ) -> Result<Pot, errors::Error> {
+        if amount <= 0 {
+            return Err(errors::ErrorKind::InvalidAmount(amount).into());
+        }
+        let uri = self.build_uri(&["pots", &pot_id, "deposit"], &[]);
+        let request = self.create_idempotent_form_request(
+            Method::PUT,
+            uri,
+            &[
+                ("source_account_id", &source_account_id),
+                ("amount", &amount.to_string()),
+                ("dedupe_id", dedupe_id.as_str()),
+            ],
+            &dedupe_id,
+        );
+
+        self.make_request(request, |body| {
+            let p: Pot = serde_json::from_slice(&body)?;
+            Ok(p)
+        }).await
+    }
+
+    /// Moves money from a pot back into an account. The `dedupe_id` makes retrying safe: Monzo
+    /// only performs the withdrawal once for the same id. Returns the updated pot.
+    pub async fn withdraw_from_pot(
+        &self,
+        pot_id: PotId,
+        destination_account_id: AccountId,
+        amount: i64,
+        dedupe_id: DedupeId,
+    ) -> Result<Pot, errors::Error> {
+        if amount <= 0 {
+            return Err(errors::ErrorKind::InvalidAmount(amount).into());
+        }
+        let uri = self.build_uri(&["pots", &pot_id, "withdraw"], &[]);
+        let request = self.create_idempotent_form_request(
+            Method::PUT,
+            uri,
+            &[
+                ("destination_account_id", &destination_account_id),
+                ("amount", &amount.to_string()),
+                ("dedupe_id", dedupe_id.as_str()),
+            ],
+            &dedupe_id,
+        );
+
+        self.make_request(request, |body| {
+            let p: Pot = serde_json::from_slice(&body)?;
+            Ok(p)
+        }).await
+    }
+
+    /// Moves money from one pot to another by withdrawing into the account and depositing from
+    /// it. The deposit is only made when the withdrawal succeeded. Both steps get their own id
+    /// derived from `dedupe_id`, so retrying the whole move with the same id stays safe. Returns
+    /// the updated source and destination pots.
+    pub async fn move_between_pots(
+        &self,
+        from_pot: PotId,
+        to_pot: PotId,
+        account_id: AccountId,
+        amount: i64,
+        dedupe_id: DedupeId,
+    ) -> Result<(Pot, Pot), errors::Error> {
+        let withdraw_id = DedupeId::from(format!("{}-withdraw", dedupe_id.as_str()));
+        let deposit_id = DedupeId::from(format!("{}-deposit", dedupe_id.as_str()));
+        let from = self
+            .withdraw_from_pot(from_pot, account_id.clone(), amount, withdraw_id)
+            .await?;
+        let to = self.deposit_into_pot(to_pot, account_id, amount, deposit_id).await?;
+        Ok((from, to))
+    }
+
+    /// Creates an item in the user’s feed for the account, showing the title, image and optional
+    /// body of `item`, styled as given. The `dedupe_id` makes retrying safe: Monzo only creates
+    /// the item once for the same id.
+    pub async fn create_feed_item(
+        &self,
+        account_id: AccountId,
+        item_type: FeedItemType,
+        item: FeedItemParams,
+        dedupe_id: DedupeId,
+    ) -> Result<(), errors::Error> {
+        let uri = self.build_uri(&["feed"], &[]);
+        let mut params = vec![
+            (Self::ACCOUNT_ID, account_id.as_str()),
+            ("type", item_type.as_str()),
+            ("params[title]", item.title.as_str()),
+            ("params[image_url]", item.image_url.as_str()),
+        ];
+        if let Some(ref body) = item.body {
+            params.push(("params[body]", body));
+        }
+        let style = &item.style;
+        let style_params = [
+            ("params[background_color]", &style.background_color),
+            ("params[title_color]", &style.title_color),
+            ("params[body_color]", &style.body_color),
+            ("url", &style.url),
+        ];
+        for &(name, value) in &style_params {
+            if let Some(ref value) = *value {
+                params.push((name, value));
+            }
+        }
+        params.push(("dedupe_id", dedupe_id.as_str()));
+        let request = self.create_idempotent_form_request(Method::POST, uri, &params, &dedupe_id);
+
+        // The response body is empty on success.
+        self.make_request(request, |_| Ok(())).await
+    }
+
+    /// Creates basic items in the feeds of the accounts, posting them concurrently up to the
+    /// concurrency limit. Every item gets its own random `DedupeId`, so retries within the call
+    /// are safe. The items that failed are listed in an `errors::ErrorKind::PartialFailure`, the
+    /// others are created regardless.
+    pub async fn create_feed_items(
+        &self,
+        items: Vec<(AccountId, FeedItemParams)>,
+    ) -> Result<(), errors::Error> {
+        let limit = self.concurrency_limit.unwrap_or(items.len()).max(1);
+        let requests = items.into_iter().enumerate().map(|(index, (account_id, item))| {
+            async move {
+                let result = self
+                    .create_feed_item(
+                        account_id.clone(),
+                        FeedItemType::Basic,
+                        item,
+                        DedupeId::random(),
+                    )
+                    .await;
+                (index, account_id, result)
+            }
+        });
+        let mut failures: Vec<_> = stream::iter(requests)
+            .buffer_unordered(limit)
+            .filter_map(|(index, account_id, result)| {
+                future::ready(result.err().map(|error| (index, account_id, error)))
+            })
+            .collect()
+            .await;
+        if failures.is_empty() {
+            return Ok(());
+        }
+        failures.sort_by_key(|&(index, _, _)| index);
+        Err(errors::ErrorKind::PartialFailure(failures).into())
+    }
+
+    /// Registers a webhook so Monzo calls the url whenever a transaction is created on the
+    /// account.
+    pub async fn register_webhook(
+        &self,
+        account_id: AccountId,
+        url: String,
+    ) -> Result<Webhook, errors::Error> {
+        let uri = self.build_uri(&["webhooks"], &[]);
+        let request = self.create_form_request(
+            Method::POST,
+            uri,
+            &[(Self::ACCOUNT_ID, &account_id), ("url", &url)],
+        );
+
+        self.make_request(request, |body| {
+            let w: WebhookResponse = serde_json::from_slice(&body)?;
+            Ok(w.webhook)
+        }).await
+    }
+
+    /// Returns the webhooks registered for the account.
+    pub async fn webhooks(&self, account_id: AccountId) -> Result<Webhooks, errors::Error> {
+        let uri = self.build_uri(&["webhooks"], &[(Self::ACCOUNT_ID, &account_id)]);
+
+        self.make_request(self.create_request(uri), |body| {
+            let w: Webhooks = serde_json::from_slice(&body)?;
+            Ok(w)
+        }).await
+    }
+
+    /// Deletes a webhook, so Monzo stops calling its url.
+    pub async fn delete_webhook(&self, webhook_id: WebhookId) -> Result<(), errors::Error> {
+        let uri = self.build_uri(&["webhooks", &webhook_id], &[]);
+        let request = self.create_form_request(Method::DELETE, uri, &[]);
+
+        // The response body is an empty object on success.
+        self.make_request(request, |_| Ok(())).await
+    }
+
+    /// Attaches key-value metadata to a transaction. An empty value deletes the key. The `notes`
+    /// key sets the notes of the transaction, which may be at most `MAX_NOTES_LENGTH` bytes.
+    /// Returns the updated transaction.
+    pub async fn annotate_transaction(
+        &self,
+        transaction_id: TransactionId,
+        metadata: HashMap<String, String>,
+    ) -> Result<TransactionResponse, errors::Error> {
+        if let Some(notes) = metadata.get("notes") {
+            if notes.len() > MAX_NOTES_LENGTH {
+                return Err(errors::ErrorKind::NotesTooLong(notes.len()).into());
+            }
+        }
+        let uri = self.build_uri(&["transactions", &transaction_id], &[]);
+        // Sorted so the body doesn't depend on the hash map order.
+        let metadata: BTreeMap<String, String> = metadata
+            .into_iter()
+            .map(|(key, value)| (format!("metadata[{}]", key), value))
+            .collect();
+        let params: Vec<(&str, &str)> = metadata
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        let request = self.create_form_request(Method::PATCH, uri, &params);
+
+        self.make_request(request, |body| {
+            let t: TransactionResponse = serde_json::from_slice(&body)?;
+            Ok(t)
+        }).await
+    }
+
+    /// Sets the notes of a transaction, which may be at most `MAX_NOTES_LENGTH` bytes. Returns the
+    /// updated transaction.
+    pub async fn set_transaction_notes(
+        &self,
+        transaction_id: TransactionId,
+        notes: String,
+    ) -> Result<TransactionResponse, errors::Error> {
+        let mut metadata = HashMap::new();
+        metadata.insert("notes".to_string(), notes);
+        self.annotate_transaction(transaction_id, metadata).await
+    }
+
+    /// Requests a temporary url to upload a file to, before attaching it with
+    /// `register_attachment`.
+    pub async fn request_attachment_upload(
+        &self,
+        file_name: String,
+        file_type: String,
+        content_length: u64,
+    ) -> Result<AttachmentUpload, errors::Error> {
+        let uri = self.build_uri(&["attachment", "upload"], &[]);
+        let request = self.create_form_request(
+            Method::POST,
+            uri,
+            &[
+                ("file_name", &file_name),
+                ("file_type", &file_type),
+                ("content_length", &content_length.to_string()),
+            ],
+        );
+
+        self.make_request(request, |body| {
+            let u: AttachmentUpload = serde_json::from_slice(&body)?;
+            Ok(u)
+        }).await
+    }
+
+    /// Attaches a file, like a receipt image, to a transaction. The file must already be hosted at
+    /// `file_url`. Returns the registered attachment.
+    pub async fn register_attachment(
+        &self,
+        external_id: TransactionId,
+        file_url: String,
+        file_type: String,
+    ) -> Result<Attachment, errors::Error> {
+        let uri = self.build_uri(&["attachment", "register"], &[]);
+        let request = self.create_form_request(
+            Method::POST,
+            uri,
+            &[
+                ("external_id", &external_id),
+                ("file_url", &file_url),
+                ("file_type", &file_type),
+            ],
+        );
+
+        self.make_request(request, |body| {
+            let a: AttachmentResponse = serde_json::from_slice(&body)?;
+            Ok(a.attachment)
+        }).await
+    }
+
+    /// Removes an attachment from its transaction.
+    pub async fn deregister_attachment(
+        &self,
+        attachment_id: AttachmentId,
+    ) -> Result<(), errors::Error> {
+        let uri = self.build_uri(&["attachment", "deregister"], &[]);
+        let request = self.create_form_request(Method::POST, uri, &[("id", &attachment_id)]);
+
+        // The response body is an empty object on success.
+        self.make_request(request, |_| Ok(())).await
     }
 }
@@ -0,0 +1,51 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use mockito::mock;
+use monzo::Client;
+use spectral::prelude::*;
+use std::sync::Mutex;
+use tokio::runtime::Runtime;
+use url::Url;
+
+// Keeps the messages logged by this crate, so the test can inspect them.
+struct CapturingLogger {
+    messages: Mutex<Vec<String>>,
+}
+
+impl Log for CapturingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with("monzo")
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let message = format!("{} {}", record.level(), record.args());
+            self.messages.lock().unwrap().push(message);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CapturingLogger = CapturingLogger {
+    messages: Mutex::new(Vec::new()),
+};
+
+#[test]
+fn logs_requests() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Debug);
+    let _m = mock("GET", "/accounts")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let base_url = Url::parse(&mockito::server_url()).unwrap();
+    let monzo = Client::new_with_base_url("secret_token", base_url);
+    runtime.block_on(monzo.accounts()).unwrap();
+
+    let messages = LOGGER.messages.lock().unwrap();
+    let expected = format!("{} GET {}/accounts -> 200 OK", Level::Debug, mockito::server_url());
+    assert_that(&*messages).contains(expected);
+    assert_that(&messages.iter().any(|m| m.contains("secret_token"))).is_false();
+}
@@ -1,18 +1,302 @@
-extern crate hyper;
-extern crate mockito;
-extern crate monzo;
-extern crate spectral;
-extern crate tokio_core;
-extern crate url;
-
+use chrono::Utc;
+use futures::StreamExt;
 use mockito::mock;
-use monzo::{Accounts, Balance, Client, PotsResponse, TransactionResponse, Transactions};
+use monzo::{
+    AccessToken, Accounts, Attachment, AttachmentUpload, Balance, Client, ClientBuilder, Currency,
+    DeclineReason, DedupeId, FeedItemParams, FeedItemStyle, FeedItemType, MerchantInfo, Pot,
+    PotsResponse, Since, Transaction, TransactionResponse, Transactions, TransactionsQuery, Webhook,
+    Webhooks, WhoAmI,
+};
 use spectral::prelude::*;
-use tokio_core::reactor::Core;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
 use url::Url;
 
 fn create_monzo() -> monzo::Client {
-    Client::new_with_base_url("token", Url::parse(mockito::SERVER_URL).unwrap())
+    Client::new_with_base_url("token", Url::parse(&mockito::server_url()).unwrap())
+}
+
+#[test]
+fn ping() {
+    let _m = mock("GET", mockito::Matcher::Regex(r"^/ping$".to_string()))
+        .match_header("Authorization", mockito::Matcher::Missing)
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"ping\": \"pong\"}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.ping();
+    assert_that(&runtime.block_on(work).unwrap()).is_equal_to(());
+}
+
+#[test]
+fn exchange_code() {
+    let _m = mock("POST", "/oauth2/token")
+        .match_header("Authorization", mockito::Matcher::Missing)
+        .match_header("Content-Type", "application/x-www-form-urlencoded")
+        .match_body(
+            "grant_type=authorization_code&client_id=oauthclient_00009&client_secret=secret\
+             &redirect_uri=https%3A%2F%2Fexample.com%2Fcallback&code=auth_code",
+        )
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"access_token\": \"access_token\",
+                \"client_id\": \"oauthclient_00009\",
+                \"expires_in\": 21600,
+                \"refresh_token\": \"refresh_token\",
+                \"token_type\": \"Bearer\",
+                \"user_id\": \"user_00009\"
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.exchange_code(
+        "oauthclient_00009",
+        "secret",
+        "https://example.com/callback",
+        "auth_code",
+    );
+    let t: AccessToken = runtime.block_on(work).unwrap();
+    assert_that(&t.access_token.as_str()).is_equal_to("access_token");
+    assert_that(&t.expires_in).is_equal_to(21600);
+    assert_that(&t.refresh_token).is_equal_to(Some("refresh_token".to_string()));
+    assert_that(&t.user_id.as_str()).is_equal_to("user_00009");
+}
+
+#[test]
+fn refresh_token() {
+    let _m = mock("POST", "/oauth2/token")
+        .match_header("Authorization", mockito::Matcher::Missing)
+        .match_body(
+            "grant_type=refresh_token&client_id=oauthclient_00009&client_secret=secret\
+             &refresh_token=old_refresh_token",
+        )
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"access_token\": \"new_token\",
+                \"client_id\": \"oauthclient_00009\",
+                \"expires_in\": 21600,
+                \"refresh_token\": \"new_refresh_token\",
+                \"token_type\": \"Bearer\",
+                \"user_id\": \"user_00009\"
+            }",
+        )
+        .create();
+    let _accounts = mock("GET", "/accounts")
+        .match_header("Authorization", "Bearer new_token")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.refresh_token("oauthclient_00009", "secret", "old_refresh_token");
+    let t: AccessToken = runtime.block_on(work).unwrap();
+    assert_that(&t.refresh_token).is_equal_to(Some("new_refresh_token".to_string()));
+
+    monzo.set_access_token(t.access_token);
+    let a: Accounts = runtime.block_on(monzo.accounts()).unwrap();
+    assert_that(&a.accounts.len()).is_equal_to(0);
+}
+
+#[test]
+fn set_access_token_shared_with_clones() {
+    let m = mock("GET", "/accounts")
+        .match_header("Authorization", "Bearer new_token")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .expect(2)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let clone = monzo.clone();
+    monzo.set_access_token("new_token".to_string());
+    runtime.block_on(monzo.accounts()).unwrap();
+    runtime.block_on(clone.accounts()).unwrap();
+    m.assert();
+}
+
+fn mock_refresh(refresh_token: &str, access_token: &str) -> mockito::Mock {
+    mock("POST", "/oauth2/token")
+        .match_body(
+            format!(
+                "grant_type=refresh_token&client_id=oauthclient_00009&client_secret=secret\
+                 &refresh_token={}",
+                refresh_token
+            ).as_str(),
+        )
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(format!(
+            "{{
+                \"access_token\": \"{}\",
+                \"client_id\": \"oauthclient_00009\",
+                \"expires_in\": 21600,
+                \"refresh_token\": \"new_refresh_token\",
+                \"token_type\": \"Bearer\",
+                \"user_id\": \"user_00009\"
+            }}",
+            access_token
+        ))
+        .expect(1)
+        .create()
+}
+
+fn mock_expired(token: &str) -> mockito::Mock {
+    mock("GET", "/accounts")
+        .match_header("Authorization", format!("Bearer {}", token).as_str())
+        .with_status(401)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"code\": \"unauthorized.bad_access_token.expired\",
+                \"error\": \"invalid_token\"
+            }",
+        )
+}
+
+#[test]
+fn auto_refresh() {
+    let expired = mock_expired("token").expect(1).create();
+    let refresh = mock_refresh("old_refresh_token", "new_token");
+    let accounts = mock("GET", "/accounts")
+        .match_header("Authorization", "Bearer new_token")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .expect(2)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo =
+        create_monzo().with_auto_refresh("oauthclient_00009", "secret", "old_refresh_token");
+    let a: Accounts = runtime.block_on(monzo.accounts()).unwrap();
+    assert_that(&a.accounts.len()).is_equal_to(0);
+    // The new token is used from then on.
+    runtime.block_on(monzo.accounts()).unwrap();
+    expired.assert();
+    refresh.assert();
+    accounts.assert();
+}
+
+#[test]
+fn auto_refresh_once() {
+    let _expired = mock_expired("token").create();
+    let _still_expired = mock_expired("new_token").create();
+    let refresh = mock_refresh("old_refresh_token", "new_token");
+    let runtime = Runtime::new().unwrap();
+    let monzo =
+        create_monzo().with_auto_refresh("oauthclient_00009", "secret", "old_refresh_token");
+    match runtime.block_on(monzo.accounts()) {
+        Err(monzo::errors::Error(monzo::errors::ErrorKind::TokenExpired, _)) => {}
+        _ => panic!("Incorrect error type"),
+    }
+    refresh.assert();
+}
+
+#[test]
+fn refresh_token_invalid_grant() {
+    let _m = mock("POST", "/oauth2/token")
+        .with_status(401)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"code\": \"unauthorized.bad_refresh_token\",
+                \"error\": \"invalid_grant\",
+                \"error_description\": \"refresh token revoked\"
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.refresh_token("oauthclient_00009", "secret", "revoked");
+
+    match runtime.block_on(work).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::BadResponse(statuscode, e), _) => {
+            assert_that(&statuscode).is_equal_to(hyper::StatusCode::UNAUTHORIZED);
+            assert_that(&e.error)
+                .is_some()
+                .is_equal_to("invalid_grant".to_string());
+        }
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn whoami() {
+    let _m = mock("GET", mockito::Matcher::Regex(r"^/ping/whoami$".to_string()))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"authenticated\": true,
+                \"client_id\": \"oauthclient_000094PvINDGzT3k6tz8jp\",
+                \"user_id\": \"user_00009237aWAIQGs6A3wD3V\"
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.whoami();
+    let w: WhoAmI = runtime.block_on(work).unwrap();
+    assert_that(&w.authenticated).is_true();
+    assert_that(&w.client_id.as_str()).is_equal_to("oauthclient_000094PvINDGzT3k6tz8jp");
+    assert_that(&w.user_id.as_str()).is_equal_to("user_00009237aWAIQGs6A3wD3V");
+}
+
+#[test]
+fn is_token_valid() {
+    let _m = mock("GET", "/ping/whoami")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"authenticated\": true,
+                \"client_id\": \"oauthclient_000094PvINDGzT3k6tz8jp\",
+                \"user_id\": \"user_00009237aWAIQGs6A3wD3V\"
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    assert_that(&runtime.block_on(monzo.is_token_valid()).unwrap()).is_true();
+}
+
+#[test]
+fn is_token_valid_unauthorized() {
+    let _m = mock("GET", "/ping/whoami")
+        .match_header("Authorization", "Bearer expired")
+        .with_status(401)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"code\": \"unauthorized.bad_access_token\"}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = Client::new_with_base_url("expired", Url::parse(&mockito::server_url()).unwrap());
+    assert_that(&runtime.block_on(monzo.is_token_valid()).unwrap()).is_false();
+}
+
+#[test]
+fn is_token_valid_network_error() {
+    // Nothing listens on the port once the listener is dropped.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+    drop(listener);
+    let runtime = Runtime::new().unwrap();
+    let monzo = Client::new_with_base_url("token", url);
+    match runtime.block_on(monzo.is_token_valid()) {
+        Err(monzo::errors::Error(monzo::errors::ErrorKind::NetworkError(_), _)) => {}
+        _ => panic!("Incorrect error type"),
+    }
 }
 
 #[test]
@@ -32,10 +316,10 @@ fn accounts() {
             }",
         )
         .create();
-    let mut core = Core::new().unwrap();
+    let runtime = Runtime::new().unwrap();
     let monzo = create_monzo();
     let work = monzo.accounts();
-    let a: Accounts = core.run(work).unwrap();
+    let a: Accounts = runtime.block_on(work).unwrap();
     assert_that(&a.accounts.len()).is_equal_to(1);
     assert_that(&a.accounts[0].id.as_str()).is_equal_to("acc_00009237aqC8c5umZmrRdh");
     assert_that(&a.accounts[0].description.as_str()).is_equal_to("Peter Pan's Account");
@@ -43,6 +327,196 @@ fn accounts() {
         .is_equal_to("2015-11-13T12:17:42+00:00".to_string());
 }
 
+#[test]
+fn accounts_full_payload() {
+    let _m = mock("GET", "/accounts")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"accounts\": [
+                    {
+                        \"id\": \"acc_00009237aqC8c5umZmrRdh\",
+                        \"description\": \"Peter Pan's Account\",
+                        \"created\": \"2015-11-13T12:17:42Z\",
+                        \"currency\": \"GBP\",
+                        \"country_code\": \"GB\",
+                        \"type\": \"uk_retail\",
+                        \"account_number\": \"12345678\",
+                        \"sort_code\": \"040004\"
+                    },
+                    {
+                        \"id\": \"acc_00009237aqC8c5umZmrRdi\",
+                        \"description\": \"Peter Pan's Prepaid Account\",
+                        \"created\": \"2015-11-13T12:17:42Z\",
+                        \"currency\": \"GBP\",
+                        \"country_code\": \"\",
+                        \"type\": \"uk_prepaid\",
+                        \"account_number\": \"\",
+                        \"sort_code\": \"\"
+                    }
+                ]
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let a: Accounts = runtime.block_on(monzo.accounts()).unwrap();
+    let retail = &a.accounts[0];
+    assert_that(&retail.currency).is_equal_to(Some(Currency::Gbp));
+    assert_that(&retail.country_code).is_equal_to(Some("GB".to_string()));
+    assert_that(&retail.account_type).is_equal_to(Some("uk_retail".to_string()));
+    assert_that(&retail.account_number).is_equal_to(Some("12345678".to_string()));
+    assert_that(&retail.sort_code).is_equal_to(Some("040004".to_string()));
+    let prepaid = &a.accounts[1];
+    assert_that(&prepaid.account_type).is_equal_to(Some("uk_prepaid".to_string()));
+    assert_that(&prepaid.country_code).is_none();
+    assert_that(&prepaid.account_number).is_none();
+    assert_that(&prepaid.sort_code).is_none();
+}
+
+#[test]
+fn accounts_filtered() {
+    let _m = mock("GET", "/accounts?account_type=uk_retail")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"accounts\": [
+                    {
+                        \"id\": \"acc_00009237aqC8c5umZmrRdh\",
+                        \"description\": \"Peter Pan's Account\",
+                        \"created\": \"2015-11-13T12:17:42Z\",
+                        \"type\": \"uk_retail\"
+                    }
+                ]
+            }",
+        )
+        .create();
+    let _unfiltered = mock("GET", "/accounts")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+
+    let work = monzo.accounts_filtered(Some("uk_retail".to_string()));
+    let a: Accounts = runtime.block_on(work).unwrap();
+    assert_that(&a.accounts.len()).is_equal_to(1);
+    assert_that(&a.accounts[0].account_type).is_equal_to(Some("uk_retail".to_string()));
+
+    let a: Accounts = runtime.block_on(monzo.accounts_filtered(None)).unwrap();
+    assert_that(&a.accounts.len()).is_equal_to(0);
+}
+
+#[test]
+fn accounts_matching() {
+    let _m = mock("GET", "/accounts")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"accounts\": [
+                    {
+                        \"id\": \"acc_1\",
+                        \"description\": \"Current account\",
+                        \"created\": \"2015-11-13T12:17:42Z\",
+                        \"type\": \"uk_retail\",
+                        \"closed\": false
+                    },
+                    {
+                        \"id\": \"acc_2\",
+                        \"description\": \"Old current account\",
+                        \"created\": \"2015-11-13T12:17:42Z\",
+                        \"type\": \"uk_retail\",
+                        \"closed\": true
+                    },
+                    {
+                        \"id\": \"acc_3\",
+                        \"description\": \"Prepaid account\",
+                        \"created\": \"2015-11-13T12:17:42Z\",
+                        \"type\": \"uk_prepaid\",
+                        \"closed\": false
+                    }
+                ]
+            }",
+        )
+        .expect(3)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let ids = |accounts: Vec<monzo::Account>| -> Vec<String> {
+        accounts.into_iter().map(|a| a.id).collect()
+    };
+
+    let work = monzo.accounts_matching(Some("uk_retail".to_string()), false);
+    assert_that(&ids(runtime.block_on(work).unwrap())).is_equal_to(vec!["acc_1".to_string()]);
+
+    let work = monzo.accounts_matching(Some("uk_retail".to_string()), true);
+    assert_that(&ids(runtime.block_on(work).unwrap()))
+        .is_equal_to(vec!["acc_1".to_string(), "acc_2".to_string()]);
+
+    let work = monzo.accounts_matching(None, false);
+    assert_that(&ids(runtime.block_on(work).unwrap()))
+        .is_equal_to(vec!["acc_1".to_string(), "acc_3".to_string()]);
+}
+
+#[test]
+fn accept_header() {
+    let _m = mock("GET", mockito::Matcher::Regex(r"^/accounts$".to_string()))
+        .match_header("Accept", "application/json")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.accounts();
+    let a: Accounts = runtime.block_on(work).unwrap();
+    assert_that(&a.accounts.len()).is_equal_to(0);
+}
+
+#[test]
+fn new_with_uri() {
+    let _m = mock("GET", mockito::Matcher::Regex(r"^/accounts$".to_string()))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let uri: hyper::Uri = mockito::server_url().parse().unwrap();
+    let monzo = Client::new_with_uri("token", uri).unwrap();
+    let a: Accounts = runtime.block_on(monzo.accounts()).unwrap();
+    assert_that(&a.accounts.len()).is_equal_to(0);
+}
+
+#[test]
+fn new_with_uri_invalid() {
+    let uri: hyper::Uri = "/relative/path".parse().unwrap();
+
+    match Client::new_with_uri("token", uri).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::InvalidUrl(_), _) => {}
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn base_url_with_path_prefix() {
+    let _m = mock("GET", mockito::Matcher::Regex(r"^/monzo/accounts$".to_string()))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    for base_url in &["/monzo/", "/monzo"] {
+        let base_url = Url::parse(&mockito::server_url()).unwrap().join(base_url).unwrap();
+        let monzo = Client::new_with_base_url("token", base_url);
+        let a: Accounts = runtime.block_on(monzo.accounts()).unwrap();
+        assert_that(&a.accounts.len()).is_equal_to(0);
+    }
+}
+
 #[test]
 fn balance() {
     let _m = mock(
@@ -58,59 +532,274 @@ fn balance() {
             }",
         )
         .create();
-    let mut core = Core::new().unwrap();
+    let runtime = Runtime::new().unwrap();
     let monzo = create_monzo();
     let work = monzo.balance("some_id".into());
-    let b: Balance = core.run(work).unwrap();
+    let b: Balance = runtime.block_on(work).unwrap();
     assert_that(&b.balance).is_equal_to(5000);
     assert_that(&b.currency.as_str()).is_equal_to("GBP");
     assert_that(&b.spend_today).is_equal_to(100);
 }
 
 #[test]
-fn transactions() {
-    let _m = mock(
-        "GET",
-        mockito::Matcher::Regex(r"^/transactions\?account_id=some_id$".to_string()),
-    ).with_status(200)
+fn balances() {
+    let mocks: Vec<mockito::Mock> = vec![("acc_1", 5000), ("acc_2", 250)]
+        .into_iter()
+        .map(|(id, balance)| {
+            mock("GET", format!("/balance?account_id={}", id).as_str())
+                .with_status(200)
+                .with_header("Content-Type", "application/json")
+                .with_body(format!(
+                    "{{\"balance\": {}, \"currency\": \"GBP\", \"spend_today\": 0}}",
+                    balance
+                ))
+                .create()
+        })
+        .collect();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.balances(vec!["acc_2".into(), "acc_1".into()]);
+    let balances: Vec<(String, i64)> = runtime
+        .block_on(work)
+        .unwrap()
+        .into_iter()
+        .map(|(id, b)| (id, b.balance))
+        .collect();
+    assert_that(&balances)
+        .is_equal_to(vec![("acc_2".to_string(), 250), ("acc_1".to_string(), 5000)]);
+    for m in mocks {
+        m.assert();
+    }
+}
+
+#[test]
+fn balances_concurrency_limit() {
+    let accounts = vec![("acc_1", 100), ("acc_2", 200), ("acc_3", 300), ("acc_4", 400)];
+    let mocks: Vec<mockito::Mock> = accounts
+        .iter()
+        .map(|&(id, balance)| {
+            mock("GET", format!("/balance?account_id={}", id).as_str())
+                .with_status(200)
+                .with_header("Content-Type", "application/json")
+                .with_body(format!(
+                    "{{\"balance\": {}, \"currency\": \"GBP\", \"spend_today\": 0}}",
+                    balance
+                ))
+                .create()
+        })
+        .collect();
+    let runtime = Runtime::new().unwrap();
+    let mut monzo = create_monzo();
+    monzo.set_concurrency_limit(2);
+    let work = monzo.balances(accounts.iter().map(|&(id, _)| id.into()).collect());
+    let balances: Vec<(String, i64)> = runtime
+        .block_on(work)
+        .unwrap()
+        .into_iter()
+        .map(|(id, b)| (id, b.balance))
+        .collect();
+    let expected: Vec<(String, i64)> =
+        accounts.into_iter().map(|(id, b)| (id.to_string(), b)).collect();
+    assert_that(&balances).is_equal_to(expected);
+    for m in mocks {
+        m.assert();
+    }
+}
+
+fn mock_accounts() -> mockito::Mock {
+    mock("GET", mockito::Matcher::Regex(r"^/accounts$".to_string()))
+        .with_status(200)
         .with_header("Content-Type", "application/json")
         .with_body(
             "{
-                \"transactions\": [
+                \"accounts\": [
                     {
-                        \"account_balance\": 13013,
-                        \"amount\": -510,
-                        \"created\": \"2015-08-22T12:20:18Z\",
-                        \"currency\": \"GBP\",
-                        \"description\": \"THE DE BEAUVOIR DELI C LONDON GBR\",
-                        \"merchant\": \"merch_00008zIcpbAKe8shBxXUtl\",
-                        \"id\": \"tx_00008zIcpb1TB4yeIFXMzx\",
-                        \"metadata\": {
-                            \"seen\": \"2015-09-15T10:19:17Z\"
-                        },
-                        \"notes\": \"Salmon sandwich 🍞\",
-                        \"is_load\": false,
-                        \"settled\": \"2015-08-23T12:20:18Z\",
-                        \"category\": \"eating_out\"
+                        \"id\": \"acc_1\",
+                        \"description\": \"Peter Pan's Account\",
+                        \"created\": \"2015-11-13T12:17:42Z\"
+                    },
+                    {
+                        \"id\": \"acc_2\",
+                        \"description\": \"Wendy's Account\",
+                        \"created\": \"2015-11-13T12:17:42Z\"
                     }
                 ]
             }",
         )
-        .create();
-    let mut core = Core::new().unwrap();
-    let monzo = create_monzo();
-    let work = monzo.transactions("some_id".into());
-    let ts: Transactions = core.run(work).unwrap();
-    assert_that(&ts.transactions.len()).is_equal_to(1);
-    let t = &ts.transactions[0];
-    assert_that(&t.account_balance).is_equal_to(13013);
-    assert_that(&t.amount).is_equal_to(-510);
-    assert_that(&t.created.to_rfc3339().as_str()).is_equal_to("2015-08-22T12:20:18+00:00");
-    assert_that(&t.currency.as_str()).is_equal_to("GBP");
-    assert_that(&t.description.as_str()).is_equal_to("THE DE BEAUVOIR DELI C LONDON GBR");
+        .create()
+}
+
+#[test]
+fn balance_default() {
+    let _m = mock(
+        "GET",
+        mockito::Matcher::Regex(r"^/balance\?account_id=some_id$".to_string()),
+    ).with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"balance\": 5000,
+                \"currency\": \"GBP\",
+                \"spend_today\": 100
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo().with_default_account("some_id".into());
+    let work = monzo.balance_default();
+    let b: Balance = runtime.block_on(work).unwrap();
+    assert_that(&b.balance).is_equal_to(5000);
+}
+
+#[test]
+fn transactions_default() {
+    let _m = mock(
+        "GET",
+        mockito::Matcher::Regex(r"^/transactions\?account_id=some_id$".to_string()),
+    ).with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"transactions\": []}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo().with_default_account("some_id".into());
+    let work = monzo.transactions_default();
+    let ts: Transactions = runtime.block_on(work).unwrap();
+    assert_that(&ts.transactions.len()).is_equal_to(0);
+}
+
+#[test]
+fn no_default_account() {
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+
+    match runtime.block_on(monzo.balance_default()).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::NoDefaultAccount, _) => {}
+        _ => panic!("Incorrect error type"),
+    }
+    match runtime.block_on(monzo.transactions_default()).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::NoDefaultAccount, _) => {}
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn new_with_token_only() {
+    // Builds its own connector, so no runtime is needed until a request is made.
+    let monzo = Client::new("token");
+    let runtime = Runtime::new().unwrap();
+
+    match runtime.block_on(monzo.balance_default()).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::NoDefaultAccount, _) => {}
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn timed() {
+    let _m = mock(
+        "GET",
+        mockito::Matcher::Regex(r"^/balance\?account_id=some_id$".to_string()),
+    ).with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"balance\": 5000,
+                \"currency\": \"GBP\",
+                \"spend_today\": 100
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo::timed(monzo.balance("some_id".into()));
+    let response = runtime.block_on(work).unwrap();
+    assert_that(&response.value.balance).is_equal_to(5000);
+    assert_that(&response.duration).is_greater_than(Duration::from_secs(0));
+}
+
+#[test]
+fn account_balance() {
+    let _a = mock_accounts();
+    let _m = mock(
+        "GET",
+        mockito::Matcher::Regex(r"^/balance\?account_id=acc_2$".to_string()),
+    ).with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"balance\": 5000,
+                \"currency\": \"GBP\",
+                \"spend_today\": 100
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.account_balance("Wendy's Account");
+    let b: Balance = runtime.block_on(work).unwrap();
+    assert_that(&b.balance).is_equal_to(5000);
+}
+
+#[test]
+fn account_balance_not_found() {
+    let _a = mock_accounts();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.account_balance("Hook's Account");
+    let response_error = runtime.block_on(work).unwrap_err();
+
+    match response_error {
+        monzo::errors::Error(monzo::errors::ErrorKind::AccountNotFound(description), _) => {
+            assert_that(&description.as_str()).is_equal_to("Hook's Account");
+        }
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn transactions() {
+    let _m = mock(
+        "GET",
+        mockito::Matcher::Regex(r"^/transactions\?account_id=some_id$".to_string()),
+    ).with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"transactions\": [
+                    {
+                        \"account_balance\": 13013,
+                        \"amount\": -510,
+                        \"created\": \"2015-08-22T12:20:18Z\",
+                        \"currency\": \"GBP\",
+                        \"description\": \"THE DE BEAUVOIR DELI C LONDON GBR\",
+                        \"merchant\": \"merch_00008zIcpbAKe8shBxXUtl\",
+                        \"id\": \"tx_00008zIcpb1TB4yeIFXMzx\",
+                        \"metadata\": {
+                            \"seen\": \"2015-09-15T10:19:17Z\"
+                        },
+                        \"notes\": \"Salmon sandwich 🍞\",
+                        \"is_load\": false,
+                        \"settled\": \"2015-08-23T12:20:18Z\",
+                        \"category\": \"eating_out\"
+                    }
+                ]
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.transactions("some_id".into());
+    let ts: Transactions = runtime.block_on(work).unwrap();
+    assert_that(&ts.transactions.len()).is_equal_to(1);
+    let t = &ts.transactions[0];
+    assert_that(&t.account_balance).is_equal_to(13013);
+    assert_that(&t.amount).is_equal_to(-510);
+    assert_that(&t.created.to_rfc3339().as_str()).is_equal_to("2015-08-22T12:20:18+00:00");
+    assert_that(&t.currency.as_str()).is_equal_to("GBP");
+    assert_that(&t.description.as_str()).is_equal_to("THE DE BEAUVOIR DELI C LONDON GBR");
     assert_that(&t.merchant)
         .is_some()
-        .is_equal_to("merch_00008zIcpbAKe8shBxXUtl".to_string());
+        .is_equal_to(MerchantInfo::Id("merch_00008zIcpbAKe8shBxXUtl".to_string()));
     assert_that(&t.id.as_str()).is_equal_to("tx_00008zIcpb1TB4yeIFXMzx");
     assert_that(&t.metadata.len()).is_equal_to(1);
     assert_that(&t.notes.as_str()).is_equal_to("Salmon sandwich 🍞");
@@ -121,52 +810,918 @@ fn transactions() {
     assert_that(&t.decline_reason).is_none();
 }
 
+fn transaction_body(id: &str, description: &str) -> String {
+    format!(
+        "{{
+            \"account_balance\": 13013,
+            \"amount\": -510,
+            \"created\": \"2015-08-22T12:20:18Z\",
+            \"currency\": \"GBP\",
+            \"description\": \"{}\",
+            \"merchant\": null,
+            \"id\": \"{}\",
+            \"metadata\": {{}},
+            \"notes\": \"\",
+            \"is_load\": false,
+            \"settled\": \"\",
+            \"category\": \"eating_out\"
+        }}",
+        description, id
+    )
+}
+
+// Serves a single request, sending the body in chunks so it arrives in pieces.
+fn serve_chunked(chunks: Vec<String>) -> Url {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..read]);
+        }
+        stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                  Transfer-Encoding: chunked\r\n\r\n",
+            )
+            .unwrap();
+        for chunk in chunks {
+            write!(stream, "{:x}\r\n{}\r\n", chunk.len(), chunk).unwrap();
+            stream.flush().unwrap();
+            thread::sleep(Duration::from_millis(10));
+        }
+        stream.write_all(b"0\r\n\r\n").unwrap();
+    });
+    url
+}
+
+#[test]
+fn transactions_stream() {
+    let body = format!(
+        "{{\"transactions\": [{}, {}, {}]}}",
+        transaction_body("tx_1", "BRACES } IN { A STRING"),
+        transaction_body("tx_2", "AN \\\"ESCAPED\\\" QUOTE"),
+        transaction_body("tx_3", "THE DE BEAUVOIR DELI C LONDON GBR")
+    );
+    // Split the body in the middle of strings and objects.
+    let chunks = body
+        .as_bytes()
+        .chunks(37)
+        .map(|chunk| String::from_utf8(chunk.to_vec()).unwrap())
+        .collect();
+    let runtime = Runtime::new().unwrap();
+    let monzo = Client::new_with_base_url("token", serve_chunked(chunks));
+    let transactions: Vec<Transaction> = runtime
+        .block_on(monzo.transactions_stream("some_id".into(), None).collect::<Vec<_>>())
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    let ids: Vec<&str> = transactions.iter().map(|t| t.id.as_str()).collect();
+    assert_that(&ids).is_equal_to(vec!["tx_1", "tx_2", "tx_3"]);
+    assert_that(&transactions[0].description.as_str()).is_equal_to("BRACES } IN { A STRING");
+    assert_that(&transactions[1].description.as_str()).is_equal_to("AN \"ESCAPED\" QUOTE");
+}
+
+#[test]
+fn all_transactions() {
+    let pages = vec![
+        ("", format!("{}, {}", transaction_body("tx_1", "A"), transaction_body("tx_2", "B"))),
+        ("&since=tx_2", transaction_body("tx_3", "C")),
+        ("&since=tx_3", String::new()),
+    ];
+    let mocks: Vec<mockito::Mock> = pages
+        .into_iter()
+        .map(|(since, transactions)| {
+            mock(
+                "GET",
+                format!("/transactions?account_id=some_id&limit=100{}", since).as_str(),
+            ).with_status(200)
+                .with_header("Content-Type", "application/json")
+                .with_body(format!("{{\"transactions\": [{}]}}", transactions))
+                .expect(1)
+                .create()
+        })
+        .collect();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let transactions: Vec<Transaction> = runtime
+        .block_on(monzo.all_transactions("some_id".into()).collect::<Vec<_>>())
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    let ids: Vec<&str> = transactions.iter().map(|t| t.id.as_str()).collect();
+    assert_that(&ids).is_equal_to(vec!["tx_1", "tx_2", "tx_3"]);
+    for m in mocks {
+        m.assert();
+    }
+}
+
+#[test]
+fn transactions_settled_only() {
+    let settled = |id| {
+        transaction_body(id, "Settled")
+            .replace("\"settled\": \"\"", "\"settled\": \"2015-08-23T12:20:18Z\"")
+    };
+    let _m = mock("GET", "/transactions?account_id=some_id")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(format!(
+            "{{\"transactions\": [{}, {}, {}]}}",
+            settled("tx_1"),
+            transaction_body("tx_2", "Pending"),
+            settled("tx_3")
+        ))
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.transactions_settled_only("some_id".into());
+    let transactions = runtime.block_on(work).unwrap().transactions;
+    let ids: Vec<&str> = transactions.iter().map(|t| t.id.as_str()).collect();
+    assert_that(&ids).is_equal_to(vec!["tx_1", "tx_3"]);
+}
+
+#[test]
+fn transactions_stream_from_cursor() {
+    let m = mock("GET", "/transactions?account_id=some_id&since=tx_2")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(format!(
+            "{{\"transactions\": [{}, {}]}}",
+            transaction_body("tx_3", "C"),
+            transaction_body("tx_4", "D")
+        ))
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let stream = monzo.transactions_stream("some_id".into(), Some(Since::Cursor("tx_2".into())));
+    let ids: Vec<String> = runtime
+        .block_on(stream.collect::<Vec<_>>())
+        .into_iter()
+        .map(|t| t.unwrap().id)
+        .collect();
+    assert_that(&ids).is_equal_to(vec!["tx_3".to_string(), "tx_4".to_string()]);
+    m.assert();
+}
+
+#[test]
+fn transactions_stream_error() {
+    let _m = mock("GET", "/transactions?account_id=some_id")
+        .with_status(403)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"code\": \"forbidden.insufficient_permissions\"}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let stream = monzo.transactions_stream("some_id".into(), None);
+    let results = runtime.block_on(stream.collect::<Vec<_>>());
+    assert_that(&results.len()).is_equal_to(1);
+    match results.into_iter().next().unwrap().unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::BadResponse(statuscode, _), _) => {
+            assert_that(&statuscode).is_equal_to(hyper::StatusCode::FORBIDDEN);
+        }
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn query_transactions() {
+    let _m = mock(
+        "GET",
+        mockito::Matcher::Regex(
+            r"^/transactions\?account_id=some_id&limit=10&since=2015-08-01T00%3A00%3A00Z&expand%5B%5D=merchant$"
+                .to_string(),
+        ),
+    ).with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"transactions\": [
+                    {
+                        \"account_balance\": 13013,
+                        \"amount\": -510,
+                        \"created\": \"2015-08-22T12:20:18Z\",
+                        \"currency\": \"GBP\",
+                        \"description\": \"THE DE BEAUVOIR DELI C LONDON GBR\",
+                        \"merchant\": {
+                            \"created\": \"2015-08-22T12:20:18Z\",
+                            \"group_id\": \"grp_00008zIcpbBOaAr7TTP3sv\",
+                            \"id\": \"merch_00008zIcpbAKe8shBxXUtl\",
+                            \"logo\": \"https://example.com/logo.png\",
+                            \"emoji\": \"🍞\",
+                            \"name\": \"The De Beauvoir Deli Co.\",
+                            \"category\": \"eating_out\"
+                        },
+                        \"id\": \"tx_00008zIcpb1TB4yeIFXMzx\",
+                        \"metadata\": {},
+                        \"notes\": \"Salmon sandwich 🍞\",
+                        \"is_load\": false,
+                        \"settled\": \"2015-08-23T12:20:18Z\",
+                        \"category\": \"eating_out\"
+                    }
+                ]
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let query = TransactionsQuery::new("some_id".into())
+        .limit(10)
+        .since(Since::Time("2015-08-01T00:00:00Z".parse().unwrap()))
+        .expand_merchant();
+    let work = monzo.query_transactions(query);
+    let t = &runtime.block_on(work).unwrap().transactions[0];
+    match t.merchant {
+        Some(MerchantInfo::Details(ref merchant)) => {
+            assert_that(&merchant.name.as_str()).is_equal_to("The De Beauvoir Deli Co.");
+        }
+        _ => panic!("Merchant was not expanded"),
+    }
+    assert_that(&t.merchant_id())
+        .is_some()
+        .is_equal_to(&"merch_00008zIcpbAKe8shBxXUtl".to_string());
+}
+
+#[test]
+fn transactions_since_id() {
+    let m = mock(
+        "GET",
+        "/transactions?account_id=some_id&limit=50&since=tx_00008zIcpb1TB4yeIFXMzx",
+    ).with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(format!("{{\"transactions\": [{}]}}", transaction_body("tx_2", "B")))
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.transactions_since_id(
+        "some_id".into(),
+        "tx_00008zIcpb1TB4yeIFXMzx".into(),
+        Some(50),
+    );
+    let t: Transactions = runtime.block_on(work).unwrap();
+    m.assert();
+    assert_that(&t.transactions[0].id.as_str()).is_equal_to("tx_2");
+}
+
+#[test]
+fn transactions_paginated() {
+    let time = "2015-08-01T00:00:00Z".parse().unwrap();
+    let cases = vec![
+        (None, None, None, ""),
+        (Some(10), None, None, "&limit=10"),
+        (
+            None,
+            Some(Since::Time(time)),
+            None,
+            "&since=2015-08-01T00%3A00%3A00Z",
+        ),
+        (
+            None,
+            Some(Since::Cursor("tx_00008zIcpb1TB4yeIFXMzx".to_string())),
+            None,
+            "&since=tx_00008zIcpb1TB4yeIFXMzx",
+        ),
+        (None, None, Some(time), "&before=2015-08-01T00%3A00%3A00Z"),
+        (
+            Some(10),
+            Some(Since::Cursor("tx_00008zIcpb1TB4yeIFXMzx".to_string())),
+            Some(time),
+            "&limit=10&since=tx_00008zIcpb1TB4yeIFXMzx&before=2015-08-01T00%3A00%3A00Z",
+        ),
+    ];
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    for (limit, since, before, query) in cases {
+        let _m = mock(
+            "GET",
+            mockito::Matcher::Regex(format!(r"^/transactions\?account_id=some_id{}$", query)),
+        ).with_status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body("{\"transactions\": []}")
+            .create();
+        let work = monzo.transactions_paginated("some_id".into(), limit, since, before);
+        let ts: Transactions = runtime.block_on(work).unwrap();
+        assert_that(&ts.transactions.len()).is_equal_to(0);
+    }
+}
+
+#[test]
+fn transactions_declined_no_merchant_no_settled() {
+    let _m = mock(
+        "GET",
+        mockito::Matcher::Regex(r"^/transactions\?account_id=some_id$".to_string()),
+    ).with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"transactions\": [
+                    {
+                        \"account_balance\": 13013,
+                        \"amount\": -510,
+                        \"created\": \"2015-08-22T12:20:18Z\",
+                        \"currency\": \"GBP\",
+                        \"description\": \"THE DE BEAUVOIR DELI C LONDON GBR\",
+                        \"merchant\": null,
+                        \"id\": \"tx_00008zIcpb1TB4yeIFXMzx\",
+                        \"metadata\": {},
+                        \"notes\": \"Salmon sandwich 🍞\",
+                        \"is_load\": false,
+                        \"settled\": \"\",
+                        \"category\": \"eating_out\",
+                        \"decline_reason\": \"CARD_INACTIVE\"
+                    }
+                ]
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.transactions("some_id".into());
+    let t = &runtime.block_on(work).unwrap().transactions[0];
+    assert_that(&t.decline_reason)
+        .is_some()
+        .is_equal_to(DeclineReason::CardInactive);
+    assert_that(&t.merchant).is_none();
+    assert_that(&t.settled).is_none();
+}
+
+#[test]
+fn transaction() {
+    let _m = mock(
+        "GET",
+        mockito::Matcher::Regex(r"^/transactions/some_t_id\?account_id=some_id$".to_string()),
+    ).with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"transaction\": {
+                    \"account_balance\": 13013,
+                    \"amount\": -510,
+                    \"created\": \"2015-08-22T12:20:18Z\",
+                    \"currency\": \"GBP\",
+                    \"description\": \"THE DE BEAUVOIR DELI C LONDON GBR\",
+                    \"merchant\": \"merch_00008zIcpbAKe8shBxXUtl\",
+                    \"id\": \"tx_00008zIcpb1TB4yeIFXMzx\",
+                    \"metadata\": {
+                        \"seen\": \"2015-09-15T10:19:17Z\"
+                    },
+                    \"notes\": \"Salmon sandwich 🍞\",
+                    \"is_load\": false,
+                    \"settled\": \"2015-08-23T12:20:18Z\",
+                    \"category\": \"eating_out\"
+                }
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.transaction("some_id".into(), "some_t_id".into());
+    let ts: TransactionResponse = runtime.block_on(work).unwrap();
+    let t = &ts.transaction;
+    assert_that(&t.account_balance).is_equal_to(13013);
+    // No point in testing Transaction deserialization further.
+}
+
+#[test]
+fn pots() {
+    let _m = mock("GET", mockito::Matcher::Regex(r"^/pots/listV1".to_string()))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"pots\": [
+                    {
+                        \"id\": \"pot_0000778xxfgh4iu8z83nWb\",
+                        \"name\": \"Savings\",
+                        \"style\": \"beach_ball\",
+                        \"balance\": 133700,
+                        \"currency\": \"GBP\",
+                        \"created\": \"2017-11-09T12:30:53.695Z\",
+                        \"updated\": \"2017-11-09T13:30:53.695Z\",
+                        \"deleted\": false
+                    }
+                ]
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.pots();
+    let pots: PotsResponse = runtime.block_on(work).unwrap();
+    let pot = &pots.pots[0];
+    assert_that(&pot.id.as_str()).is_equal_to("pot_0000778xxfgh4iu8z83nWb");
+    assert_that(&pot.name.as_str()).is_equal_to("Savings");
+    assert_that(&pot.style.as_str()).is_equal_to("beach_ball");
+    assert_that(&pot.balance).is_equal_to(133700);
+    assert_that(&pot.currency.as_str()).is_equal_to("GBP");
+    assert_that(&pot.created.to_rfc3339()).is_equal_to("2017-11-09T12:30:53.695+00:00".to_string());
+    assert_that(&pot.updated.to_rfc3339()).is_equal_to("2017-11-09T13:30:53.695+00:00".to_string());
+    assert_that(&pot.deleted).is_equal_to(false);
+}
+
+fn pot_body(balance: i64) -> String {
+    format!(
+        "{{
+            \"id\": \"pot_0000778xxfgh4iu8z83nWb\",
+            \"name\": \"Savings\",
+            \"style\": \"beach_ball\",
+            \"balance\": {},
+            \"currency\": \"GBP\",
+            \"created\": \"2017-11-09T12:30:53.695Z\",
+            \"updated\": \"2017-11-09T13:30:53.695Z\",
+            \"deleted\": false
+        }}",
+        balance
+    )
+}
+
+#[test]
+fn pots_for_account() {
+    let _m = mock("GET", "/pots?current_account_id=acc_1")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(format!("{{\"pots\": [{}]}}", pot_body(133700)))
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.pots_for_account("acc_1".into());
+    let pots: PotsResponse = runtime.block_on(work).unwrap();
+    assert_that(&pots.pots.len()).is_equal_to(1);
+    assert_that(&pots.pots[0].id.as_str()).is_equal_to("pot_0000778xxfgh4iu8z83nWb");
+}
+
+#[test]
+fn active_pots() {
+    let deleted = pot_body(0)
+        .replace("pot_0000778xxfgh4iu8z83nWb", "pot_deleted")
+        .replace("\"deleted\": false", "\"deleted\": true");
+    let _m = mock("GET", "/pots?current_account_id=acc_1")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(format!("{{\"pots\": [{}, {}]}}", deleted, pot_body(133700)))
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.active_pots("acc_1".into());
+    let pots: PotsResponse = runtime.block_on(work).unwrap();
+    assert_that(&pots.pots.len()).is_equal_to(1);
+    assert_that(&pots.pots[0].id.as_str()).is_equal_to("pot_0000778xxfgh4iu8z83nWb");
+}
+
+#[test]
+fn create_pot() {
+    let body = pot_body(0).replace(
+        "\"deleted\": false",
+        "\"deleted\": false, \"goal_amount\": 100000",
+    );
+    let m = mock("POST", "/pots")
+        .match_header("Content-Type", "application/x-www-form-urlencoded")
+        .match_body("pot_current_account_id=acc_1&name=Savings&goal_amount=100000")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(body)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.create_pot("acc_1".into(), "Savings".into(), Some(100000));
+    let pot: Pot = runtime.block_on(work).unwrap();
+    m.assert();
+    assert_that(&pot.name.as_str()).is_equal_to("Savings");
+    assert_that(&pot.goal_amount).is_equal_to(Some(100000));
+}
+
+#[test]
+fn create_pot_empty_name() {
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    match runtime.block_on(monzo.create_pot("acc_1".into(), " ".into(), None)) {
+        Err(monzo::errors::Error(monzo::errors::ErrorKind::EmptyPotName, _)) => {}
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn delete_pot() {
+    let _m = mock("DELETE", "/pots/pot_0000778xxfgh4iu8z83nWb")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(pot_body(0).replace("\"deleted\": false", "\"deleted\": true"))
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.delete_pot("pot_0000778xxfgh4iu8z83nWb".into());
+    let pot = runtime.block_on(work).unwrap().unwrap();
+    assert_that(&pot.deleted).is_true();
+
+    let _m = mock("DELETE", "/pots/pot_1")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{}")
+        .create();
+    let work = monzo.delete_pot("pot_1".into());
+    assert_that(&runtime.block_on(work).unwrap()).is_none();
+}
+
+#[test]
+fn deposit_into_pot() {
+    let _m = mock(
+        "PUT",
+        mockito::Matcher::Regex(r"^/pots/pot_0000778xxfgh4iu8z83nWb/deposit$".to_string()),
+    ).match_header("Content-Type", "application/x-www-form-urlencoded")
+        .match_header("Idempotency-Key", "some_dedupe_id")
+        .match_body("source_account_id=some_id&amount=1000&dedupe_id=some_dedupe_id")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(pot_body(134700))
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.deposit_into_pot(
+        "pot_0000778xxfgh4iu8z83nWb".into(),
+        "some_id".into(),
+        1000,
+        "some_dedupe_id".into(),
+    );
+    let pot: Pot = runtime.block_on(work).unwrap();
+    assert_that(&pot.balance).is_equal_to(134700);
+}
+
+#[test]
+fn deposit_into_pot_same_dedupe_id() {
+    let dedupe_id = DedupeId::random();
+    let m = mock("PUT", "/pots/pot_0000778xxfgh4iu8z83nWb/deposit")
+        .match_body(
+            format!(
+                "source_account_id=some_id&amount=1000&dedupe_id={}",
+                dedupe_id.as_str()
+            ).as_str(),
+        )
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(pot_body(134700))
+        .expect(2)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    for _ in 0..2 {
+        let work = monzo.deposit_into_pot(
+            "pot_0000778xxfgh4iu8z83nWb".into(),
+            "some_id".into(),
+            1000,
+            dedupe_id.clone(),
+        );
+        runtime.block_on(work).unwrap();
+    }
+    m.assert();
+}
+
+#[test]
+fn move_between_pots() {
+    let withdraw = mock("PUT", "/pots/pot_from/withdraw")
+        .match_body("destination_account_id=some_id&amount=500&dedupe_id=move-1-withdraw")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(pot_body(1500).replace("pot_0000778xxfgh4iu8z83nWb", "pot_from"))
+        .create();
+    let deposit = mock("PUT", "/pots/pot_to/deposit")
+        .match_body("source_account_id=some_id&amount=500&dedupe_id=move-1-deposit")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(pot_body(2500).replace("pot_0000778xxfgh4iu8z83nWb", "pot_to"))
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.move_between_pots(
+        "pot_from".into(),
+        "pot_to".into(),
+        "some_id".into(),
+        500,
+        "move-1".into(),
+    );
+    let (from, to) = runtime.block_on(work).unwrap();
+    withdraw.assert();
+    deposit.assert();
+    assert_that(&from.id).is_equal_to("pot_from".to_string());
+    assert_that(&from.balance).is_equal_to(1500);
+    assert_that(&to.id).is_equal_to("pot_to".to_string());
+    assert_that(&to.balance).is_equal_to(2500);
+}
+
+#[test]
+fn move_between_pots_failed_withdraw() {
+    let _withdraw = mock("PUT", "/pots/pot_empty/withdraw")
+        .with_status(400)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"code\": \"bad_request.insufficient_funds\"}")
+        .create();
+    let deposit = mock("PUT", "/pots/pot_other/deposit")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(pot_body(2500))
+        .expect(0)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.move_between_pots(
+        "pot_empty".into(),
+        "pot_other".into(),
+        "some_id".into(),
+        500,
+        "move-2".into(),
+    );
+    match runtime.block_on(work) {
+        Err(monzo::errors::Error(monzo::errors::ErrorKind::BadResponse(status, _), _)) => {
+            assert_that(&status).is_equal_to(hyper::StatusCode::BAD_REQUEST)
+        }
+        _ => panic!("Incorrect error type"),
+    }
+    deposit.assert();
+}
+
+#[test]
+fn withdraw_from_pot() {
+    let _m = mock(
+        "PUT",
+        mockito::Matcher::Regex(r"^/pots/pot_0000778xxfgh4iu8z83nWb/withdraw$".to_string()),
+    ).match_header("Content-Type", "application/x-www-form-urlencoded")
+        .match_body("destination_account_id=some_id&amount=700&dedupe_id=some_dedupe_id")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(pot_body(133000))
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.withdraw_from_pot(
+        "pot_0000778xxfgh4iu8z83nWb".into(),
+        "some_id".into(),
+        700,
+        "some_dedupe_id".into(),
+    );
+    let pot: Pot = runtime.block_on(work).unwrap();
+    assert_that(&pot.balance).is_equal_to(133000);
+}
+
+#[test]
+fn withdraw_from_pot_invalid_amount() {
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+
+    for amount in &[0, -700] {
+        let work = monzo.withdraw_from_pot(
+            "pot_0000778xxfgh4iu8z83nWb".into(),
+            "some_id".into(),
+            *amount,
+            "some_dedupe_id".into(),
+        );
+        match runtime.block_on(work).unwrap_err() {
+            monzo::errors::Error(monzo::errors::ErrorKind::InvalidAmount(a), _) => {
+                assert_that(&a).is_equal_to(*amount);
+            }
+            _ => panic!("Incorrect error type"),
+        }
+    }
+}
+
+#[test]
+fn create_feed_item() {
+    let _m = mock("POST", mockito::Matcher::Regex(r"^/feed$".to_string()))
+        .match_body(
+            "account_id=some_id&type=basic&params%5Btitle%5D=Hello&\
+             params%5Bimage_url%5D=https%3A%2F%2Fexample.com%2Fimage.png&params%5Bbody%5D=World&\
+             dedupe_id=feed_1",
+        )
+        .match_header("Idempotency-Key", "feed_1")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let item = FeedItemParams {
+        body: Some("World".into()),
+        ..FeedItemParams::new("Hello", "https://example.com/image.png")
+    };
+    let work = monzo.create_feed_item("some_id".into(), FeedItemType::Basic, item, "feed_1".into());
+    assert_that(&runtime.block_on(work).unwrap()).is_equal_to(());
+}
+
+#[test]
+fn create_feed_item_styled() {
+    let m = mock("POST", "/feed")
+        .match_body(
+            "account_id=some_id&type=basic&params%5Btitle%5D=Hello&\
+             params%5Bimage_url%5D=https%3A%2F%2Fexample.com%2Fimage.png&\
+             params%5Bbackground_color%5D=%23FCF1EE&params%5Btitle_color%5D=%23333333&\
+             url=https%3A%2F%2Fexample.com&dedupe_id=feed_2",
+        )
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let style = FeedItemStyle {
+        background_color: Some("#FCF1EE".into()),
+        title_color: Some("#333333".into()),
+        url: Some("https://example.com".into()),
+        ..FeedItemStyle::default()
+    };
+    let item = FeedItemParams {
+        style,
+        ..FeedItemParams::new("Hello", "https://example.com/image.png")
+    };
+    let work = monzo.create_feed_item("some_id".into(), FeedItemType::Basic, item, "feed_2".into());
+    runtime.block_on(work).unwrap();
+    m.assert();
+}
+
+#[test]
+fn create_feed_items_partial_failure() {
+    let ok = mock("POST", "/feed")
+        .match_body(mockito::Matcher::Regex("^account_id=acc_1&".to_string()))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("")
+        .create();
+    let failed = mock("POST", "/feed")
+        .match_body(mockito::Matcher::Regex("^account_id=acc_2&".to_string()))
+        .with_status(500)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"code\": \"internal_service\"}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let items = vec![
+        ("acc_1".into(), FeedItemParams::new("Hello", "https://example.com/image.png")),
+        ("acc_2".into(), FeedItemParams::new("Hello", "https://example.com/image.png")),
+    ];
+    match runtime.block_on(monzo.create_feed_items(items)) {
+        Err(monzo::errors::Error(monzo::errors::ErrorKind::PartialFailure(failures), _)) => {
+            assert_that(&failures.len()).is_equal_to(1);
+            let (index, ref account_id, ref error) = failures[0];
+            assert_that(&index).is_equal_to(1);
+            assert_that(account_id).is_equal_to("acc_2".to_string());
+            assert_that(&error.status_code())
+                .is_equal_to(Some(hyper::StatusCode::INTERNAL_SERVER_ERROR));
+        }
+        _ => panic!("Incorrect error type"),
+    }
+    ok.assert();
+    failed.assert();
+}
+
+#[test]
+fn register_webhook() {
+    let _m = mock("POST", mockito::Matcher::Regex(r"^/webhooks$".to_string()))
+        .match_body("account_id=some_id&url=http%3A%2F%2Fexample.com%2Fcallback")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"webhook\": {
+                    \"account_id\": \"some_id\",
+                    \"id\": \"webhook_0000760ezZrwceHKhdzH6N\",
+                    \"url\": \"http://example.com/callback\"
+                }
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.register_webhook("some_id".into(), "http://example.com/callback".into());
+    let w: Webhook = runtime.block_on(work).unwrap();
+    assert_that(&w.id.as_str()).is_equal_to("webhook_0000760ezZrwceHKhdzH6N");
+    assert_that(&w.account_id.as_str()).is_equal_to("some_id");
+    assert_that(&w.url.as_str()).is_equal_to("http://example.com/callback");
+}
+
 #[test]
-fn transactions_declined_no_merchant_no_settled() {
+fn webhooks() {
     let _m = mock(
         "GET",
-        mockito::Matcher::Regex(r"^/transactions\?account_id=some_id$".to_string()),
+        mockito::Matcher::Regex(r"^/webhooks\?account_id=some_id$".to_string()),
     ).with_status(200)
         .with_header("Content-Type", "application/json")
         .with_body(
             "{
-                \"transactions\": [
+                \"webhooks\": [
                     {
-                        \"account_balance\": 13013,
-                        \"amount\": -510,
-                        \"created\": \"2015-08-22T12:20:18Z\",
-                        \"currency\": \"GBP\",
-                        \"description\": \"THE DE BEAUVOIR DELI C LONDON GBR\",
-                        \"merchant\": null,
-                        \"id\": \"tx_00008zIcpb1TB4yeIFXMzx\",
-                        \"metadata\": {},
-                        \"notes\": \"Salmon sandwich 🍞\",
-                        \"is_load\": false,
-                        \"settled\": \"\",
-                        \"category\": \"eating_out\",
-                        \"decline_reason\": \"CARD_INACTIVE\"
+                        \"account_id\": \"some_id\",
+                        \"id\": \"webhook_1\",
+                        \"url\": \"http://example.com/callback\"
+                    },
+                    {
+                        \"account_id\": \"some_id\",
+                        \"id\": \"webhook_2\",
+                        \"url\": \"http://example.com/other\"
                     }
                 ]
             }",
         )
         .create();
-    let mut core = Core::new().unwrap();
+    let runtime = Runtime::new().unwrap();
     let monzo = create_monzo();
-    let work = monzo.transactions("some_id".into());
-    let t = &core.run(work).unwrap().transactions[0];
-    assert_that(&t.decline_reason)
-        .is_some()
-        .is_equal_to("CARD_INACTIVE".to_string());
-    assert_that(&t.merchant).is_none();
-    assert_that(&t.settled).is_none();
+    let work = monzo.webhooks("some_id".into());
+    let w: Webhooks = runtime.block_on(work).unwrap();
+    assert_that(&w.webhooks.len()).is_equal_to(2);
+    assert_that(&w.webhooks[0].id.as_str()).is_equal_to("webhook_1");
+    assert_that(&w.webhooks[1].id.as_str()).is_equal_to("webhook_2");
 }
 
 #[test]
-fn transaction() {
+fn delete_webhook() {
+    let _m = mock("DELETE", mockito::Matcher::Regex(r"^/webhooks/webhook_1$".to_string()))
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.delete_webhook("webhook_1".into());
+    assert_that(&runtime.block_on(work).unwrap()).is_equal_to(());
+}
+
+#[test]
+fn request_attachment_upload() {
+    let _m = mock("POST", "/attachment/upload")
+        .match_body("file_name=receipt.png&file_type=image%2Fpng&content_length=12345")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"file_url\": \"https://s3-eu-west-1.amazonaws.com/mondo-image-uploads/receipt.png\",
+                \"upload_url\": \"https://mondo-image-uploads.s3.amazonaws.com/receipt.png?sig=1\"
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.request_attachment_upload("receipt.png".into(), "image/png".into(), 12345);
+    let u: AttachmentUpload = runtime.block_on(work).unwrap();
+    assert_that(&u.file_url.as_str())
+        .is_equal_to("https://s3-eu-west-1.amazonaws.com/mondo-image-uploads/receipt.png");
+    assert_that(&u.upload_url.as_str())
+        .is_equal_to("https://mondo-image-uploads.s3.amazonaws.com/receipt.png?sig=1");
+}
+
+#[test]
+fn register_attachment() {
+    let _m = mock("POST", "/attachment/register")
+        .match_body(
+            "external_id=tx_00008zIcpb1TB4yeIFXMzx\
+             &file_url=https%3A%2F%2Fexample.com%2Freceipt.png&file_type=image%2Fpng",
+        )
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+                \"attachment\": {
+                    \"id\": \"attach_00009238aOAIvVqfb9LrZh\",
+                    \"user_id\": \"user_00009238aMBIIrS5Rdncq9\",
+                    \"external_id\": \"tx_00008zIcpb1TB4yeIFXMzx\",
+                    \"file_url\": \"https://example.com/receipt.png\",
+                    \"file_type\": \"image/png\",
+                    \"created\": \"2015-11-12T18:37:02Z\"
+                }
+            }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.register_attachment(
+        "tx_00008zIcpb1TB4yeIFXMzx".into(),
+        "https://example.com/receipt.png".into(),
+        "image/png".into(),
+    );
+    let a: Attachment = runtime.block_on(work).unwrap();
+    assert_that(&a.id.as_str()).is_equal_to("attach_00009238aOAIvVqfb9LrZh");
+    assert_that(&a.external_id.as_str()).is_equal_to("tx_00008zIcpb1TB4yeIFXMzx");
+    assert_that(&a.file_type.as_str()).is_equal_to("image/png");
+}
+
+#[test]
+fn deregister_attachment() {
+    let _m = mock("POST", "/attachment/deregister")
+        .match_body("id=attach_00009238aOAIvVqfb9LrZh")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let work = monzo.deregister_attachment("attach_00009238aOAIvVqfb9LrZh".into());
+    assert_that(&runtime.block_on(work).unwrap()).is_equal_to(());
+}
+
+#[test]
+fn annotate_transaction() {
     let _m = mock(
-        "GET",
-        mockito::Matcher::Regex(r"^/transactions/some_t_id\?account_id=some_id$".to_string()),
-    ).with_status(200)
+        "PATCH",
+        mockito::Matcher::Regex(r"^/transactions/tx_00008zIcpb1TB4yeIFXMzx$".to_string()),
+    ).match_body("metadata%5Bnotes%5D=Lunch&metadata%5Bseen%5D=")
+        .with_status(200)
         .with_header("Content-Type", "application/json")
         .with_body(
             "{
@@ -179,9 +1734,9 @@ fn transaction() {
                     \"merchant\": \"merch_00008zIcpbAKe8shBxXUtl\",
                     \"id\": \"tx_00008zIcpb1TB4yeIFXMzx\",
                     \"metadata\": {
-                        \"seen\": \"2015-09-15T10:19:17Z\"
+                        \"notes\": \"Lunch\"
                     },
-                    \"notes\": \"Salmon sandwich 🍞\",
+                    \"notes\": \"Lunch\",
                     \"is_load\": false,
                     \"settled\": \"2015-08-23T12:20:18Z\",
                     \"category\": \"eating_out\"
@@ -189,50 +1744,433 @@ fn transaction() {
             }",
         )
         .create();
-    let mut core = Core::new().unwrap();
+    let runtime = Runtime::new().unwrap();
     let monzo = create_monzo();
-    let work = monzo.transaction("some_id".into(), "some_t_id".into());
-    let ts: TransactionResponse = core.run(work).unwrap();
-    let t = &ts.transaction;
-    assert_that(&t.account_balance).is_equal_to(13013);
-    // No point in testing Transaction deserialization further.
+    let mut metadata = HashMap::new();
+    metadata.insert("notes".to_string(), "Lunch".to_string());
+    // An empty value deletes the key and must be sent as is.
+    metadata.insert("seen".to_string(), "".to_string());
+    let work = monzo.annotate_transaction("tx_00008zIcpb1TB4yeIFXMzx".into(), metadata);
+    let t = runtime.block_on(work).unwrap().transaction;
+    assert_that(&t.metadata.len()).is_equal_to(1);
+    assert_that(&t.metadata["notes"].as_str()).is_equal_to("Lunch");
 }
 
 #[test]
-fn pots() {
-    let _m = mock("GET", mockito::Matcher::Regex(r"^/pots/listV1".to_string()))
+fn set_transaction_notes() {
+    let _m = mock("PATCH", "/transactions/tx_00008zIcpb1TB4yeIFXMzx")
+        .match_body("metadata%5Bnotes%5D=Salmon+sandwich")
         .with_status(200)
         .with_header("Content-Type", "application/json")
         .with_body(
             "{
-                \"pots\": [
-                    {
-                        \"id\": \"pot_0000778xxfgh4iu8z83nWb\",
-                        \"name\": \"Savings\",
-                        \"style\": \"beach_ball\",
-                        \"balance\": 133700,
-                        \"currency\": \"GBP\",
-                        \"created\": \"2017-11-09T12:30:53.695Z\",
-                        \"updated\": \"2017-11-09T13:30:53.695Z\",
-                        \"deleted\": false
-                    }
-                ]
+                \"transaction\": {
+                    \"account_balance\": 13013,
+                    \"amount\": -510,
+                    \"created\": \"2015-08-22T12:20:18Z\",
+                    \"currency\": \"GBP\",
+                    \"description\": \"THE DE BEAUVOIR DELI C LONDON GBR\",
+                    \"merchant\": \"merch_00008zIcpbAKe8shBxXUtl\",
+                    \"id\": \"tx_00008zIcpb1TB4yeIFXMzx\",
+                    \"metadata\": {
+                        \"notes\": \"Salmon sandwich\"
+                    },
+                    \"notes\": \"Salmon sandwich\",
+                    \"is_load\": false,
+                    \"settled\": \"2015-08-23T12:20:18Z\",
+                    \"category\": \"eating_out\"
+                }
             }",
         )
         .create();
-    let mut core = Core::new().unwrap();
+    let runtime = Runtime::new().unwrap();
     let monzo = create_monzo();
-    let work = monzo.pots();
-    let pots: PotsResponse = core.run(work).unwrap();
-    let pot = &pots.pots[0];
-    assert_that(&pot.id.as_str()).is_equal_to("pot_0000778xxfgh4iu8z83nWb");
-    assert_that(&pot.name.as_str()).is_equal_to("Savings");
-    assert_that(&pot.style.as_str()).is_equal_to("beach_ball");
-    assert_that(&pot.balance).is_equal_to(133700);
-    assert_that(&pot.currency.as_str()).is_equal_to("GBP");
-    assert_that(&pot.created.to_rfc3339()).is_equal_to("2017-11-09T12:30:53.695+00:00".to_string());
-    assert_that(&pot.updated.to_rfc3339()).is_equal_to("2017-11-09T13:30:53.695+00:00".to_string());
-    assert_that(&pot.deleted).is_equal_to(false);
+    let work =
+        monzo.set_transaction_notes("tx_00008zIcpb1TB4yeIFXMzx".into(), "Salmon sandwich".into());
+    let t = runtime.block_on(work).unwrap().transaction;
+    assert_that(&t.notes.as_str()).is_equal_to("Salmon sandwich");
+}
+
+#[test]
+fn annotate_transaction_notes_too_long() {
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let mut metadata = HashMap::new();
+    metadata.insert("notes".to_string(), "a".repeat(monzo::MAX_NOTES_LENGTH + 1));
+    let work = monzo.annotate_transaction("tx_00008zIcpb1TB4yeIFXMzx".into(), metadata);
+
+    match runtime.block_on(work).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::NotesTooLong(length), _) => {
+            assert_that(&length).is_equal_to(monzo::MAX_NOTES_LENGTH + 1);
+        }
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn retry_rate_limited() {
+    let rate_limited = mock("GET", "/accounts")
+        .with_status(429)
+        .with_header("Retry-After", "0")
+        .with_body("{}")
+        .expect(1)
+        .create();
+    let ok = mock("GET", "/accounts")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .expect(1)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let mut monzo = create_monzo();
+    monzo.set_max_retries(1);
+    let a: Accounts = runtime.block_on(monzo.accounts()).unwrap();
+    assert_that(&a.accounts.len()).is_equal_to(0);
+    rate_limited.assert();
+    ok.assert();
+}
+
+// Serves a connection per response, closing it right after reading the request for None.
+fn serve_responses(responses: Vec<Option<&'static str>>) -> Url {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+    thread::spawn(move || {
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let read = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+            }
+            if let Some(body) = response {
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                ).unwrap();
+            }
+        }
+    });
+    url
+}
+
+#[test]
+fn retry_connection_closed() {
+    let base_url = serve_responses(vec![None, Some("{\"accounts\": []}")]);
+    let runtime = Runtime::new().unwrap();
+    let mut monzo = Client::new_with_base_url("token", base_url);
+    monzo.set_max_retries(1);
+    let a: Accounts = runtime.block_on(monzo.accounts()).unwrap();
+    assert_that(&a.accounts.len()).is_equal_to(0);
+
+    // Writes without an idempotency key aren't retried, Monzo may have handled them already.
+    let base_url = serve_responses(vec![None, Some("{}")]);
+    let mut monzo = Client::new_with_base_url("token", base_url);
+    monzo.set_max_retries(1);
+    let work = monzo.register_webhook("some_id".into(), "http://example.com/callback".into());
+    match runtime.block_on(work) {
+        Err(monzo::errors::Error(monzo::errors::ErrorKind::NetworkError(_), _)) => {}
+        _ => panic!("Incorrect error type"),
+    }
+
+    // Unless they carry one.
+    let base_url = serve_responses(vec![None, Some("{}")]);
+    let mut monzo = Client::new_with_base_url("token", base_url);
+    monzo.set_max_retries(1);
+    let item = FeedItemParams::new("Hello", "https://example.com/image.png");
+    let work = monzo.create_feed_item("some_id".into(), FeedItemType::Basic, item, "feed_1".into());
+    runtime.block_on(work).unwrap();
+
+    // Without retries the error comes through.
+    let base_url = serve_responses(vec![None]);
+    let monzo = Client::new_with_base_url("token", base_url);
+    match runtime.block_on(monzo.accounts()) {
+        Err(monzo::errors::Error(monzo::errors::ErrorKind::NetworkError(_), _)) => {}
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn no_retry_write_on_server_error() {
+    let m = mock("POST", "/webhooks")
+        .with_status(500)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"code\": \"internal_service\"}")
+        .expect(1)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let mut monzo = create_monzo();
+    monzo.set_max_retries(1);
+    let work = monzo.register_webhook("some_id".into(), "http://example.com/callback".into());
+    match runtime.block_on(work) {
+        Err(monzo::errors::Error(monzo::errors::ErrorKind::BadResponse(status, _), _)) => {
+            assert_that(&status).is_equal_to(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+        }
+        _ => panic!("Incorrect error type"),
+    }
+    m.assert();
+}
+
+#[test]
+fn rate_limited() {
+    let cases = vec![(Some("30"), Some(30)), (Some("soon"), None), (None, None)];
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    for (header, expected) in cases {
+        let mut m = mock("GET", "/accounts").with_status(429);
+        if let Some(header) = header {
+            m = m.with_header("Retry-After", header);
+        }
+        let _m = m.create();
+
+        match runtime.block_on(monzo.accounts()).unwrap_err() {
+            monzo::errors::Error(monzo::errors::ErrorKind::RateLimited(retry_after), _) => {
+                assert_that(&retry_after).is_equal_to(expected);
+            }
+            _ => panic!("Incorrect error type"),
+        }
+    }
+}
+
+#[test]
+fn no_retry_on_client_error() {
+    let m = mock("GET", "/accounts")
+        .with_status(403)
+        .with_header("Retry-After", "0")
+        .with_body("{\"code\": \"forbidden.insufficient_permissions\"}")
+        .expect(1)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let mut monzo = create_monzo();
+    monzo.set_max_retries(3);
+
+    match runtime.block_on(monzo.accounts()).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::BadResponse(statuscode, _), _) => {
+            assert_that(&statuscode).is_equal_to(hyper::StatusCode::FORBIDDEN);
+        }
+        _ => panic!("Incorrect error type"),
+    }
+    m.assert();
+}
+
+#[test]
+fn error_status_code_html_body() {
+    let _m = mock("GET", "/accounts")
+        .with_status(502)
+        .with_header("Content-Type", "text/html")
+        .with_body("<html><body><h1>502 Bad Gateway</h1></body></html>")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    let error = runtime.block_on(monzo.accounts()).unwrap_err();
+    assert_that(&error.status_code()).is_equal_to(Some(hyper::StatusCode::BAD_GATEWAY));
+    match error {
+        monzo::errors::Error(monzo::errors::ErrorKind::BadResponse(_, e), _) => {
+            assert_that(&*e).is_equal_to(monzo::Error::default());
+        }
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn retry_after_seconds() {
+    let _m = mock("GET", "/accounts")
+        .with_status(429)
+        .with_header("Retry-After", " 120 ")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    match runtime.block_on(monzo.accounts()).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::RateLimited(retry_after), _) => {
+            assert_that(&retry_after).is_equal_to(Some(120));
+        }
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn no_retry_after_long_delay() {
+    let rate_limited = mock("GET", "/accounts")
+        .with_status(429)
+        .with_header("Retry-After", "86400")
+        .expect(1)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let mut monzo = create_monzo();
+    monzo.set_max_retries(3);
+    let start = Instant::now();
+    match runtime.block_on(monzo.accounts()).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::RateLimited(retry_after), _) => {
+            assert_that(&retry_after).is_equal_to(Some(86400));
+        }
+        _ => panic!("Incorrect error type"),
+    }
+    assert_that(&start.elapsed()).is_less_than(Duration::from_secs(1));
+    rate_limited.assert();
+}
+
+#[test]
+fn retry_after_http_date() {
+    let date = (Utc::now() + chrono::Duration::seconds(120))
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+    let _m = mock("GET", "/accounts")
+        .with_status(429)
+        .with_header("Retry-After", &date)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    match runtime.block_on(monzo.accounts()).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::RateLimited(Some(retry_after)), _) => {
+            assert_that(&retry_after).is_greater_than_or_equal_to(118);
+            assert_that(&retry_after).is_less_than_or_equal_to(120);
+        }
+        _ => panic!("Incorrect error type"),
+    }
+
+    // A date that has passed means retrying right away, instead of backing off for a second.
+    let rate_limited = mock("GET", "/accounts")
+        .with_status(429)
+        .with_header("Retry-After", "Wed, 21 Oct 2015 07:28:00 GMT")
+        .expect(1)
+        .create();
+    let ok = mock("GET", "/accounts")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .expect(1)
+        .create();
+    let mut monzo = create_monzo();
+    monzo.set_max_retries(1);
+    let start = Instant::now();
+    runtime.block_on(monzo.accounts()).unwrap();
+    assert_that(&start.elapsed()).is_less_than(Duration::from_millis(900));
+    rate_limited.assert();
+    ok.assert();
+}
+
+#[test]
+fn timeout() {
+    // Connections queue up on the listener but are never answered.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+    let runtime = Runtime::new().unwrap();
+    let mut monzo = Client::new_with_base_url("token", base_url);
+    monzo.set_timeout(Duration::from_millis(100));
+
+    match runtime.block_on(monzo.accounts()).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::Timeout, _) => {}
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn with_http_client() {
+    let _m = mock("GET", "/accounts")
+        .match_header("Authorization", "Bearer token")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    // A plain http client, without tls.
+    let http_client = hyper::Client::builder().build_http();
+    let monzo = Client::with_http_client(
+        http_client,
+        "token",
+        Url::parse(&mockito::server_url()).unwrap(),
+    );
+    let a: Accounts = runtime.block_on(monzo.accounts()).unwrap();
+    assert_that(&a.accounts.len()).is_equal_to(0);
+}
+
+#[test]
+fn debug_hides_credentials() {
+    let base_url = Url::parse(&mockito::server_url()).unwrap();
+    let monzo = Client::new_with_base_url("access_1", base_url)
+        .with_auto_refresh("oauthclient_1", "secret_1", "refresh_1");
+    let debug = format!("{:?}", monzo);
+    assert_that(&debug.contains("oauthclient_1")).is_true();
+    for credential in &["access_1", "secret_1", "refresh_1"] {
+        assert_that(&debug.contains(credential)).is_false();
+    }
+
+    let builder = ClientBuilder::new().access_token("access_1");
+    assert_that(&format!("{:?}", builder).contains("access_1")).is_false();
+}
+
+#[test]
+fn client_builder() {
+    let _m = mock("GET", "/accounts")
+        .match_header("Authorization", "Bearer token")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = ClientBuilder::new()
+        .access_token("token")
+        .base_url(Url::parse(&mockito::server_url()).unwrap())
+        .timeout(Duration::from_secs(10))
+        .build();
+    let a: Accounts = runtime.block_on(monzo.accounts()).unwrap();
+    assert_that(&a.accounts.len()).is_equal_to(0);
+
+    // The timeout is applied too.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let monzo = Client::builder()
+        .access_token("token")
+        .base_url(Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap())
+        .timeout(Duration::from_millis(100))
+        .build();
+    match runtime.block_on(monzo.accounts()).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::Timeout, _) => {}
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn client_builder_timer() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    // The request runs on a runtime without timers, the timeout uses the one handed to the client.
+    let timer = Runtime::new().unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .unwrap();
+    let monzo = Client::builder()
+        .access_token("token")
+        .base_url(Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap())
+        .timeout(Duration::from_millis(100))
+        .timer(timer.handle().clone())
+        .build();
+    match runtime.block_on(monzo.accounts()).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::Timeout, _) => {}
+        _ => panic!("Incorrect error type"),
+    }
+}
+
+#[test]
+fn max_idle_connections() {
+    let _m = mock("GET", "/accounts")
+        .with_status(200)
+        .with_header("Content-Type", "application/json")
+        .with_body("{\"accounts\": []}")
+        .expect(2)
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = Client::builder()
+        .access_token("token")
+        .base_url(Url::parse(&mockito::server_url()).unwrap())
+        .max_idle_connections(0)
+        .build();
+    for _ in 0..2 {
+        let a: Accounts = runtime.block_on(monzo.accounts()).unwrap();
+        assert_that(&a.accounts.len()).is_equal_to(0);
+    }
 }
 
 #[test]
@@ -245,16 +2183,16 @@ fn unauthorized() {
         .with_body(
             "{
             \"code\": \"unauthorized.bad_access_token\",
-            \"error\": \"invalid_token\",
+            \"error\": \"invalid_client\",
             \"error_description\": \"expired1\",
             \"message\": \"expired2\"
         }",
         )
         .create();
-    let mut core = Core::new().unwrap();
+    let runtime = Runtime::new().unwrap();
     let monzo = create_monzo();
     let work = monzo.balance("some_id".into());
-    let response_error = core.run(work).unwrap_err();
+    let response_error = runtime.block_on(work).unwrap_err();
 
     match response_error {
         monzo::errors::Error(monzo::errors::ErrorKind::BadResponse(statuscode, e), _) => {
@@ -264,7 +2202,7 @@ fn unauthorized() {
                 .is_equal_to("unauthorized.bad_access_token".to_string());
             assert_that(&e.error)
                 .is_some()
-                .is_equal_to("invalid_token".to_string());
+                .is_equal_to("invalid_client".to_string());
             assert_that(&e.error_description)
                 .is_some()
                 .is_equal_to("expired1".to_string());
@@ -276,6 +2214,28 @@ fn unauthorized() {
     }
 }
 
+#[test]
+fn token_expired() {
+    let _m = mock("GET", "/balance?account_id=some_id")
+        .with_status(401)
+        .with_header("Content-Type", "application/json")
+        .with_body(
+            "{
+            \"code\": \"unauthorized.bad_access_token.expired\",
+            \"error\": \"invalid_token\",
+            \"error_description\": \"expired\",
+            \"message\": \"Access token has expired\"
+        }",
+        )
+        .create();
+    let runtime = Runtime::new().unwrap();
+    let monzo = create_monzo();
+    match runtime.block_on(monzo.balance("some_id".into())).unwrap_err() {
+        monzo::errors::Error(monzo::errors::ErrorKind::TokenExpired, _) => {}
+        _ => panic!("Incorrect error type"),
+    }
+}
+
 #[test]
 fn bad_json() {
     let _m = mock(
@@ -285,13 +2245,15 @@ fn bad_json() {
         .with_header("Content-Type", "application/json")
         .with_body("{ badjson ")
         .create();
-    let mut core = Core::new().unwrap();
+    let runtime = Runtime::new().unwrap();
     let monzo = create_monzo();
     let work = monzo.balance("some_id".into());
-    let response_error = core.run(work).unwrap_err();
+    let response_error = runtime.block_on(work).unwrap_err();
 
     match response_error {
-        monzo::errors::Error(monzo::errors::ErrorKind::BadJsonResponse(_), _) => {}
+        monzo::errors::Error(monzo::errors::ErrorKind::UnexpectedBody(body, _), _) => {
+            assert_that(&body.as_str()).is_equal_to("{ badjson ");
+        }
         _ => panic!("Incorrect error type"),
     }
 }
@@ -0,0 +1,822 @@
+use hyper::StatusCode;
+use monzo::{
+    Account, Attachment, Balance, Category, CategorySummary, Counterparty, Currency, DeclineReason,
+    DedupeId, FeedItemType, Merchant, Money, Pot, Transaction, TransactionType, Transactions,
+};
+use monzo::errors::{Error, ErrorKind};
+use serde_json::Value;
+use spectral::prelude::*;
+
+fn transaction_json(id: &str, amount: i64, category: &str, merchant: Option<&str>) -> Value {
+    let json = format!(
+        "{{
+            \"account_balance\": 13013,
+            \"amount\": {},
+            \"created\": \"2015-08-22T12:20:18Z\",
+            \"currency\": \"GBP\",
+            \"description\": \"THE DE BEAUVOIR DELI C LONDON GBR\",
+            \"merchant\": {},
+            \"id\": \"{}\",
+            \"metadata\": {{}},
+            \"notes\": \"\",
+            \"is_load\": false,
+            \"settled\": \"2015-08-23T12:20:18Z\",
+            \"category\": \"{}\",
+            \"scheme\": \"mastercard\"
+        }}",
+        amount,
+        merchant.map_or("null".to_string(), |m| format!("\"{}\"", m)),
+        id,
+        category
+    );
+    serde_json::from_str(&json).unwrap()
+}
+
+fn transaction(id: &str, amount: i64, category: &str, merchant: Option<&str>) -> Transaction {
+    serde_json::from_value(transaction_json(id, amount, category, merchant)).unwrap()
+}
+
+#[test]
+fn amount_histogram() {
+    let ts = Transactions {
+        transactions: vec![
+            transaction("tx_1", -510, "eating_out", None),
+            transaction("tx_2", -999, "eating_out", None),
+            transaction("tx_3", -1500, "shopping", None),
+        ],
+    };
+    let histogram = ts.amount_histogram(1000);
+    assert_that(&histogram.len()).is_equal_to(2);
+    assert_that(&histogram[&0]).is_equal_to(2);
+    assert_that(&histogram[&1000]).is_equal_to(1);
+}
+
+#[test]
+fn amount_histogram_empty_and_zero_bucket() {
+    let empty = Transactions {
+        transactions: vec![],
+    };
+    assert_that(&empty.amount_histogram(1000).is_empty()).is_true();
+
+    let ts = Transactions {
+        transactions: vec![transaction("tx_1", -510, "eating_out", None)],
+    };
+    assert_that(&ts.amount_histogram(0).is_empty()).is_true();
+}
+
+#[test]
+fn spend_by_merchant() {
+    let ts = Transactions {
+        transactions: vec![
+            transaction("tx_1", -510, "eating_out", Some("merch_1")),
+            transaction("tx_2", -490, "eating_out", Some("merch_1")),
+            transaction("tx_3", -1500, "shopping", Some("merch_2")),
+            transaction("tx_4", -200, "general", None),
+            transaction("tx_5", 2000, "mondo", None),
+        ],
+    };
+    let totals = ts.spend_by_merchant();
+    assert_that(&totals.len()).is_equal_to(2);
+    assert_that(&totals["merch_1"]).is_equal_to(1000);
+    assert_that(&totals["merch_2"]).is_equal_to(1500);
+}
+
+#[test]
+fn spend_by_category() {
+    let mut top_up = transaction_json("tx_6", -300, "mondo", None);
+    top_up["is_load"] = Value::Bool(true);
+    let ts = Transactions {
+        transactions: vec![
+            transaction("tx_1", -510, "eating_out", Some("merch_1")),
+            transaction("tx_2", -490, "eating_out", None),
+            transaction("tx_3", -1500, "shopping", Some("merch_2")),
+            declined("tx_4", "INSUFFICIENT_FUNDS"),
+            transaction("tx_5", 2000, "shopping", None),
+            serde_json::from_value(top_up).unwrap(),
+        ],
+    };
+    let totals = ts.spend_by_category();
+    assert_that(&totals.len()).is_equal_to(2);
+    assert_that(&totals[&Category::EatingOut]).is_equal_to(1000);
+    assert_that(&totals[&Category::Shopping]).is_equal_to(1500);
+}
+
+#[test]
+fn category_summary() {
+    let mut top_up = transaction_json("tx_7", -300, "mondo", None);
+    top_up["is_load"] = Value::Bool(true);
+    let ts = Transactions {
+        transactions: vec![
+            transaction("tx_1", -510, "eating_out", None),
+            transaction("tx_2", -491, "eating_out", None),
+            transaction("tx_3", -1500, "shopping", None),
+            transaction("tx_4", -200, "transport", None),
+            declined("tx_5", "INSUFFICIENT_FUNDS"),
+            transaction("tx_6", 2000, "shopping", None),
+            serde_json::from_value(top_up).unwrap(),
+        ],
+    };
+    assert_that(&ts.category_summary()).is_equal_to(vec![
+        CategorySummary {
+            category: Category::Shopping,
+            count: 1,
+            total_minor: 1500,
+            average_minor: 1500,
+        },
+        CategorySummary {
+            category: Category::EatingOut,
+            count: 2,
+            total_minor: 1001,
+            average_minor: 500,
+        },
+        CategorySummary {
+            category: Category::Transport,
+            count: 1,
+            total_minor: 200,
+            average_minor: 200,
+        },
+    ]);
+}
+
+#[test]
+fn to_qif() {
+    let mut json = transaction_json("tx_2", 2000, "general", None);
+    json["description"] = Value::String("Refund".to_string());
+    json["notes"] = Value::String("returned\nshoes".to_string());
+    let ts = Transactions {
+        transactions: vec![
+            transaction("tx_1", -510, "eating_out", None),
+            serde_json::from_value(json).unwrap(),
+            declined("tx_3", "CARD_BLOCKED"),
+        ],
+    };
+    let lines = [
+        "!Type:Bank",
+        "D08/22/2015",
+        "T-5.10",
+        "PTHE DE BEAUVOIR DELI C LONDON GBR",
+        "^",
+        "D08/22/2015",
+        "T20.00",
+        "PRefund",
+        "Mreturned shoes",
+        "^",
+    ];
+    assert_that(&ts.to_qif()).is_equal_to(lines.join("\n") + "\n");
+}
+
+#[test]
+fn in_amount_range() {
+    let ts = Transactions {
+        transactions: vec![
+            transaction("tx_1", -10000, "shopping", None),
+            transaction("tx_2", -7500, "shopping", None),
+            transaction("tx_3", -5000, "bills", None),
+            transaction("tx_4", -4999, "eating_out", None),
+            transaction("tx_5", 6000, "general", None),
+            transaction("tx_6", -10001, "holidays", None),
+        ],
+    };
+    let ids: Vec<&str> = ts
+        .in_amount_range(-10000, -5000)
+        .iter()
+        .map(|t| t.id.as_str())
+        .collect();
+    assert_that(&ids).is_equal_to(vec!["tx_1", "tx_2", "tx_3"]);
+    assert_that(&ts.in_amount_range(0, 10000).len()).is_equal_to(1);
+    assert_that(&ts.in_amount_range(1, -1)).is_empty();
+}
+
+#[test]
+fn transaction_type() {
+    let purchase = transaction("tx_1", -510, "eating_out", Some("merch_1"));
+    assert_that(&purchase.transaction_type()).is_equal_to(TransactionType::Purchase);
+
+    let mut json = transaction_json("tx_2", 10000, "mondo", None);
+    json["is_load"] = Value::Bool(true);
+    let top_up: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&top_up.transaction_type()).is_equal_to(TransactionType::TopUp);
+
+    let refund = transaction("tx_3", 510, "eating_out", Some("merch_1"));
+    assert_that(&refund.transaction_type()).is_equal_to(TransactionType::Refund);
+
+    let mut json = transaction_json("tx_4", -2000, "general", None);
+    json["scheme"] = Value::String("uk_retail_pot".to_string());
+    let pot_transfer: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&pot_transfer.transaction_type()).is_equal_to(TransactionType::PotTransfer);
+
+    let mut json = transaction_json("tx_5", -2000, "bills", None);
+    json["scheme"] = Value::String("payport_faster_payments".to_string());
+    let bank_transfer: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&bank_transfer.transaction_type()).is_equal_to(TransactionType::BankTransfer);
+
+    let atm = transaction("tx_6", -2000, "cash", None);
+    assert_that(&atm.transaction_type()).is_equal_to(TransactionType::Atm);
+
+    let mut json = transaction_json("tx_7", -510, "eating_out", Some("merch_1"));
+    json["decline_reason"] = Value::String("INSUFFICIENT_FUNDS".to_string());
+    let declined: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&declined.transaction_type()).is_equal_to(TransactionType::Declined);
+}
+
+#[test]
+fn filter_by_category() {
+    let ts = Transactions {
+        transactions: vec![
+            transaction("tx_1", -510, "eating_out", None),
+            transaction("tx_2", -1500, "shopping", None),
+            transaction("tx_3", -490, "eating_out", None),
+        ],
+    };
+    let eating_out = ts.filter_by_category(Category::EatingOut);
+    assert_that(&eating_out.len()).is_equal_to(2);
+    assert_that(&eating_out[0].id.as_str()).is_equal_to("tx_1");
+    assert_that(&eating_out[1].id.as_str()).is_equal_to("tx_3");
+    assert_that(&ts.filter_by_category(Category::Holidays).is_empty()).is_true();
+}
+
+#[test]
+fn capability_flags() {
+    let mut json = transaction_json("tx_1", -510, "eating_out", None);
+    json["can_add_to_tab"] = Value::Bool(true);
+    json["can_split_the_bill"] = Value::Bool(false);
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&t.can_add_to_tab).is_some().is_true();
+    assert_that(&t.can_split_the_bill).is_some().is_false();
+    assert_that(&t.can_be_excluded_from_breakdown).is_none();
+    assert_that(&t.can_be_made_subscription).is_none();
+}
+
+#[test]
+fn dedupe_id_random() {
+    let id = DedupeId::random();
+    assert_that(&id.as_str().len()).is_equal_to(36);
+    assert_that(&DedupeId::random()).is_not_equal_to(&id);
+    assert_that(&DedupeId::from("deposit_1").as_str()).is_equal_to("deposit_1");
+}
+
+#[test]
+fn dedupe() {
+    let mut ts = Transactions {
+        transactions: vec![
+            transaction("tx_1", -510, "eating_out", None),
+            transaction("tx_2", -1500, "shopping", None),
+        ],
+    };
+    ts.transactions.extend(vec![
+        transaction("tx_2", -1500, "shopping", None),
+        transaction("tx_3", -490, "eating_out", None),
+    ]);
+    ts.dedupe();
+    let ids: Vec<&str> = ts.transactions.iter().map(|t| t.id.as_str()).collect();
+    assert_that(&ids).is_equal_to(vec!["tx_1", "tx_2", "tx_3"]);
+}
+
+fn balance(balance: i64) -> Balance {
+    Balance {
+        balance,
+        currency: Currency::Gbp,
+        spend_today: 0,
+    }
+}
+
+#[test]
+fn balance_equality() {
+    assert_that(&balance(5000)).is_equal_to(balance(5000));
+    assert_that(&balance(5000)).is_not_equal_to(balance(5001));
+}
+
+#[test]
+fn transaction_equality() {
+    let t = transaction("tx_1", -510, "eating_out", Some("merch_1"));
+    assert_that(&t.clone()).is_equal_to(&t);
+    assert_that(&transaction("tx_1", -511, "eating_out", Some("merch_1"))).is_not_equal_to(&t);
+}
+
+#[test]
+fn is_overdrawn() {
+    assert_that(&balance(-1).is_overdrawn()).is_true();
+    assert_that(&balance(0).is_overdrawn()).is_false();
+    assert_that(&balance(5000).is_overdrawn()).is_false();
+}
+
+#[test]
+fn currency_decimal_places() {
+    assert_that(&Currency::Gbp.decimal_places()).is_equal_to(2);
+    assert_that(&Currency::Eur.decimal_places()).is_equal_to(2);
+    assert_that(&Currency::Jpy.decimal_places()).is_equal_to(0);
+    assert_that(&Currency::from("KRW").decimal_places()).is_equal_to(0);
+    assert_that(&Currency::from("HUF").decimal_places()).is_equal_to(2);
+    assert_that(&Currency::from("KWD").decimal_places()).is_equal_to(3);
+    assert_that(&Money::new(-1250, Currency::from("KWD")).to_string())
+        .is_equal_to("-1.250 KWD".to_string());
+}
+
+#[test]
+fn currency_deserialize() {
+    let currencies: Vec<Currency> = serde_json::from_str("[\"GBP\", \"JPY\", \"NZD\"]").unwrap();
+    assert_that(&currencies).is_equal_to(vec![
+        Currency::Gbp,
+        Currency::Jpy,
+        Currency::Other("NZD".to_string()),
+    ]);
+}
+
+#[test]
+fn money_gbp() {
+    let money = Money::new(-510, Currency::Gbp);
+    assert_that(&money.to_string()).is_equal_to("-£5.10".to_string());
+    assert_that(&money.major_units()).is_equal_to(-5.1);
+    assert_that(&Money::from_major(-5.1, Currency::Gbp)).is_equal_to(money);
+    assert_that(&balance(5).balance_money().to_string()).is_equal_to("£0.05".to_string());
+}
+
+#[test]
+fn money_eur() {
+    let money = Money::new(123456, Currency::Eur);
+    assert_that(&money.to_string()).is_equal_to("€1234.56".to_string());
+    assert_that(&money.major_units()).is_equal_to(1234.56);
+    assert_that(&Money::from_major(1234.56, Currency::Eur)).is_equal_to(money);
+}
+
+#[test]
+fn money_zero_decimal_currency() {
+    let money = Money::new(-1500, Currency::Jpy);
+    assert_that(&money.to_string()).is_equal_to("-¥1500".to_string());
+    assert_that(&money.major_units()).is_equal_to(-1500.0);
+    assert_that(&Money::from_major(-1500.0, Currency::Jpy)).is_equal_to(money);
+}
+
+#[test]
+fn money_unknown_currency() {
+    let money = Money::new(510, Currency::Chf);
+    assert_that(&money.to_string()).is_equal_to("5.10 CHF".to_string());
+    let money = Money::new(510, Currency::from("NZD"));
+    assert_that(&money.to_string()).is_equal_to("5.10 NZD".to_string());
+}
+
+#[test]
+fn money_sum() {
+    let amounts = vec![
+        Money::new(-510, Currency::Gbp),
+        Money::new(1000, Currency::Gbp),
+        Money::new(-5, Currency::Gbp),
+    ];
+    assert_that(&Money::sum(Currency::Gbp, amounts))
+        .is_equal_to(Some(Money::new(485, Currency::Gbp)));
+    assert_that(&Money::sum(Currency::Gbp, vec![]))
+        .is_equal_to(Some(Money::zero(Currency::Gbp)));
+}
+
+#[test]
+fn money_sum_mixed_currencies() {
+    let amounts = vec![Money::new(-510, Currency::Gbp), Money::new(1000, Currency::Eur)];
+    assert_that(&Money::sum(Currency::Gbp, amounts)).is_none();
+    let amounts = vec![Money::new(1000, Currency::Eur)];
+    assert_that(&Money::sum(Currency::Gbp, amounts)).is_none();
+}
+
+#[test]
+fn transaction_money_domestic() {
+    let t = transaction("tx_1", -510, "eating_out", None);
+    assert_that(&t.money()).is_equal_to(Money::new(-510, Currency::Gbp));
+    assert_that(&t.local_money()).is_none();
+
+    let t = transaction("tx_2", 2000, "general", None);
+    assert_that(&t.money()).is_equal_to(Money::new(2000, Currency::Gbp));
+}
+
+#[test]
+fn transaction_money_foreign() {
+    let mut json = transaction_json("tx_1", -1285, "travel", None);
+    json["local_amount"] = Value::from(-1500);
+    json["local_currency"] = Value::String("EUR".to_string());
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&t.money()).is_equal_to(Money::new(-1285, Currency::Gbp));
+    assert_that(&t.local_money()).is_equal_to(Some(Money::new(-1500, Currency::Eur)));
+}
+
+#[test]
+fn transaction_local_amount() {
+    let mut json = transaction_json("tx_1", -1285, "travel", None);
+    json["local_amount"] = Value::from(-1500);
+    json["local_currency"] = Value::String("EUR".to_string());
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&t.local_amount).is_equal_to(Some(-1500));
+    assert_that(&t.local_currency).is_equal_to(Some(Currency::Eur));
+
+    let t = transaction("tx_2", -510, "eating_out", None);
+    assert_that(&t.local_amount).is_none();
+    assert_that(&t.local_currency).is_none();
+}
+
+#[test]
+fn transaction_counterparty() {
+    let mut json = transaction_json("tx_1", 2500, "general", None);
+    json["scheme"] = Value::String("payport_faster_payments".to_string());
+    json["counterparty"] = serde_json::from_str(
+        "{
+            \"account_number\": \"12345678\",
+            \"name\": \"Peter Pan\",
+            \"sort_code\": \"040004\",
+            \"user_id\": \"anonuser_123\"
+        }",
+    ).unwrap();
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    let counterparty = t.counterparty.unwrap();
+    assert_that(&counterparty.name).is_equal_to(Some("Peter Pan".to_string()));
+    assert_that(&counterparty.sort_code).is_equal_to(Some("040004".to_string()));
+
+    let mut json = transaction_json("tx_2", -510, "eating_out", None);
+    json["counterparty"] = serde_json::from_str("{}").unwrap();
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&t.counterparty).is_equal_to(Some(Counterparty::default()));
+}
+
+#[test]
+fn display_transaction() {
+    let t = transaction("tx_1", -510, "eating_out", None);
+    assert_that(&t.to_string())
+        .is_equal_to("-£5.10 THE DE BEAUVOIR DELI C LONDON GBR (eating_out)".to_string());
+
+    let mut json = transaction_json("tx_2", -1500, "holidays", None);
+    json["currency"] = Value::String("JPY".to_string());
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&t.to_string())
+        .is_equal_to("-¥1500 THE DE BEAUVOIR DELI C LONDON GBR (holidays)".to_string());
+}
+
+#[test]
+fn transaction_round_trip() {
+    let mut json = transaction_json("tx_1", -510, "eating_out", Some("merch_1"));
+    json["metadata"]["note"] = Value::String("lunch".to_string());
+    let t: Transaction = serde_json::from_value(json.clone()).unwrap();
+    assert_that(&serde_json::to_value(&t).unwrap()).is_equal_to(json);
+
+    let mut json = transaction_json("tx_2", -7000, "shopping", None);
+    json["settled"] = Value::String(String::new());
+    json["decline_reason"] = Value::String("CARD_BLOCKED".to_string());
+    let t: Transaction = serde_json::from_value(json.clone()).unwrap();
+    assert_that(&serde_json::to_value(&t).unwrap()).is_equal_to(json);
+
+    // An empty value means the key is to be deleted, so it has to survive serializing.
+    let mut json = transaction_json("tx_3", -510, "eating_out", None);
+    json["metadata"]["seen"] = Value::String(String::new());
+    let t: Transaction = serde_json::from_value(json.clone()).unwrap();
+    assert_that(&t.metadata.get("seen")).is_equal_to(Some(&String::new()));
+    assert_that(&serde_json::to_value(&t).unwrap()).is_equal_to(json);
+}
+
+#[test]
+fn clone_transaction() {
+    let mut json = transaction_json("tx_1", -510, "eating_out", Some("merch_1"));
+    json["metadata"]["note"] = Value::String("lunch".to_string());
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    let clone = t.clone();
+    assert_that(&clone.id).is_equal_to(&t.id);
+    assert_that(&clone.amount).is_equal_to(t.amount);
+    assert_that(&clone.created).is_equal_to(t.created);
+    assert_that(&clone.merchant).is_equal_to(&t.merchant);
+    assert_that(&clone.metadata).is_equal_to(&t.metadata);
+    assert_that(&clone.settled).is_equal_to(t.settled);
+    assert_that(&clone.category).is_equal_to(&t.category);
+}
+
+#[test]
+fn scheme_and_is_settled() {
+    let t = transaction("tx_1", -510, "eating_out", None);
+    assert_that(&t.scheme).is_equal_to("mastercard".to_string());
+    assert_that(&t.is_settled()).is_true();
+
+    let mut json = transaction_json("tx_2", 2500, "general", None);
+    json["scheme"] = Value::String("payport_faster_payments".to_string());
+    json["settled"] = Value::String(String::new());
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&t.scheme).is_equal_to("payport_faster_payments".to_string());
+    assert_that(&t.is_settled()).is_false();
+}
+
+#[test]
+fn invalid_settled() {
+    let mut json = transaction_json("tx_1", -510, "eating_out", None);
+    json["settled"] = Value::String("2015-13-45T99:00:00Z".to_string());
+    let error = serde_json::from_value::<Transaction>(json).unwrap_err().to_string();
+    assert_that(&error).contains("2015-13-45T99:00:00Z");
+    assert_that(&error).contains("out of range");
+}
+
+#[test]
+fn merchant_address() {
+    let merchant: Merchant = serde_json::from_str(
+        "{
+            \"id\": \"merch_00008zIcpbAKe8shBxXUtl\",
+            \"group_id\": \"grp_00008zIcpbBOaAr7TTP3sv\",
+            \"created\": \"2015-08-22T12:20:18Z\",
+            \"name\": \"The De Beauvoir Deli Co.\",
+            \"logo\": \"https://example.com/logo.png\",
+            \"emoji\": \"🍞\",
+            \"category\": \"eating_out\",
+            \"address\": {
+                \"short_formatted\": \"98 Southgate Road, London N1 3JD\",
+                \"formatted\": \"98 Southgate Road\\nLondon N1 3JD\",
+                \"city\": \"London\",
+                \"latitude\": 51.54151,
+                \"longitude\": -0.08482400000002599,
+                \"postcode\": \"N1 3JD\",
+                \"region\": \"\",
+                \"country\": \"GBR\"
+            }
+        }",
+    ).unwrap();
+    let address = merchant.address.unwrap();
+    assert_that(&address.short_formatted.as_str()).is_equal_to("98 Southgate Road, London N1 3JD");
+    assert_that(&address.formatted.as_str()).is_equal_to("98 Southgate Road\nLondon N1 3JD");
+    assert_that(&address.city).is_equal_to(Some("London".to_string()));
+    assert_that(&address.latitude).is_equal_to(Some(51.54151));
+    assert_that(&address.longitude).is_equal_to(Some(-0.08482400000002599));
+    assert_that(&address.postcode).is_equal_to(Some("N1 3JD".to_string()));
+    assert_that(&address.region).is_none();
+    assert_that(&address.country.as_str()).is_equal_to("GBR");
+}
+
+#[test]
+fn merchant_metadata() {
+    let merchant: Merchant = serde_json::from_str(
+        "{
+            \"id\": \"merch_00008zIcpbAKe8shBxXUtl\",
+            \"group_id\": \"grp_00008zIcpbBOaAr7TTP3sv\",
+            \"created\": \"2015-08-22T12:20:18Z\",
+            \"name\": \"Amazon\",
+            \"logo\": \"https://example.com/logo.png\",
+            \"emoji\": \"📦\",
+            \"category\": \"shopping\",
+            \"metadata\": {
+                \"online\": \"true\",
+                \"suggested_tags\": \"#shopping\",
+                \"website\": \"https://amazon.co.uk\"
+            }
+        }",
+    ).unwrap();
+    assert_that(&merchant.metadata.len()).is_equal_to(3);
+    assert_that(&merchant.metadata.get("online").map(String::as_str)).is_equal_to(Some("true"));
+}
+
+#[test]
+fn feed_item_type() {
+    assert_that(&FeedItemType::Basic.as_str()).is_equal_to("basic");
+    assert_that(&serde_json::to_string(&FeedItemType::Basic).unwrap())
+        .is_equal_to("\"basic\"".to_string());
+}
+
+#[test]
+fn error_status_code() {
+    let api_error = monzo::Error {
+        code: Some("unauthorized.bad_access_token".to_string()),
+        error: None,
+        error_description: None,
+        message: None,
+    };
+    let cases: Vec<(ErrorKind, Option<StatusCode>)> = vec![
+        (
+            ErrorKind::BadResponse(StatusCode::UNAUTHORIZED, Box::new(api_error)),
+            Some(StatusCode::UNAUTHORIZED),
+        ),
+        (ErrorKind::RateLimited(Some(30)), Some(StatusCode::TOO_MANY_REQUESTS)),
+        (ErrorKind::TokenExpired, Some(StatusCode::UNAUTHORIZED)),
+        (ErrorKind::AccountNotFound("Peter Pan's Account".to_string()), None),
+        (ErrorKind::InvalidAmount(0), None),
+        (ErrorKind::NotesTooLong(5001), None),
+        (ErrorKind::EmptyPotName, None),
+        (ErrorKind::Timeout, None),
+        (ErrorKind::NoDefaultAccount, None),
+        (ErrorKind::PartialFailure(vec![]), None),
+    ];
+    for (kind, status) in cases {
+        assert_that(&Error::from(kind).status_code()).is_equal_to(status);
+    }
+    let json_error = serde_json::from_str::<Value>("{").unwrap_err();
+    assert_that(&Error::from(json_error).status_code()).is_none();
+}
+
+#[test]
+fn metadata_empty_array() {
+    let mut json = transaction_json("tx_1", -510, "eating_out", None);
+    json["metadata"] = Value::Array(vec![]);
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&t.metadata.is_empty()).is_true();
+
+    let mut json = transaction_json("tx_1", -510, "eating_out", None);
+    json["metadata"] = Value::Array(vec![Value::String("seen".to_string())]);
+    assert_that(&serde_json::from_value::<Transaction>(json).is_err()).is_true();
+}
+
+fn pot(deleted: bool) -> Pot {
+    let json = format!(
+        "{{
+            \"id\": \"pot_0000778xxfgh4iu8z83nWb\",
+            \"name\": \"Savings\",
+            \"style\": \"beach_ball\",
+            \"balance\": 133700,
+            \"currency\": \"GBP\",
+            \"created\": \"2017-11-09T12:30:53.695Z\",
+            \"updated\": \"2017-11-09T13:30:53.695Z\",
+            \"deleted\": {}
+        }}",
+        deleted
+    );
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn pot_accepts_currency() {
+    let pot = pot(false);
+    assert_that(&pot.accepts_currency("GBP")).is_true();
+    assert_that(&pot.accepts_currency("EUR")).is_false();
+}
+
+#[test]
+fn pot_is_active() {
+    let pots = [pot(false), pot(true)];
+    let active: Vec<&Pot> = pots.iter().filter(|p| p.is_active()).collect();
+    assert_that(&active).is_equal_to(vec![&pots[0]]);
+    assert_that(&pots[0].balance_money()).is_equal_to(Money::new(133700, Currency::Gbp));
+    assert_that(&pots[0].balance_money().major_units()).is_equal_to(1337.0);
+}
+
+#[test]
+fn truncate_notes() {
+    let short = "Salmon sandwich 🍞";
+    assert_that(&monzo::truncate_notes(short).as_str()).is_equal_to(short);
+
+    // The sandwich emoji is four bytes and straddles the limit.
+    let long = format!("{}🍞", "a".repeat(monzo::MAX_NOTES_LENGTH - 2));
+    let truncated = monzo::truncate_notes(&long);
+    assert_that(&truncated.len()).is_equal_to(monzo::MAX_NOTES_LENGTH - 2);
+    assert_that(&truncated.ends_with('a')).is_true();
+
+    let exact = format!("{}🍞", "a".repeat(monzo::MAX_NOTES_LENGTH - 4));
+    assert_that(&monzo::truncate_notes(&exact)).is_equal_to(exact.clone());
+}
+
+fn account_created(created: &str) -> String {
+    let json = format!(
+        "{{
+            \"id\": \"acc_00009237aqC8c5umZmrRdh\",
+            \"description\": \"Peter Pan's Account\",
+            \"created\": {}
+        }}",
+        created
+    );
+    let account: Account = serde_json::from_str(&json).unwrap();
+    account.created.to_rfc3339()
+}
+
+#[test]
+fn created_rfc3339_or_epoch() {
+    assert_that(&account_created("\"2015-11-13T12:17:42Z\""))
+        .is_equal_to("2015-11-13T12:17:42+00:00".to_string());
+    assert_that(&account_created("1447417062"))
+        .is_equal_to("2015-11-13T12:17:42+00:00".to_string());
+    assert_that(&account_created("1447417062695"))
+        .is_equal_to("2015-11-13T12:17:42.695+00:00".to_string());
+}
+
+#[test]
+fn pot_and_attachment_epoch_timestamps() {
+    let mut json = serde_json::to_value(pot(false)).unwrap();
+    json["updated"] = Value::from(1510234253);
+    let pot: Pot = serde_json::from_value(json).unwrap();
+    assert_that(&pot.updated.to_rfc3339()).is_equal_to("2017-11-09T13:30:53+00:00".to_string());
+
+    let attachment: Attachment = serde_json::from_str(
+        "{
+            \"id\": \"attach_00009238aOAIvVqfb9LrZh\",
+            \"user_id\": \"user_00009238aMBIIrS5Rdncq9\",
+            \"external_id\": \"tx_00008zIcpb1TB4yeIFXMzx\",
+            \"file_url\": \"https://example.com/receipt.png\",
+            \"file_type\": \"image/png\",
+            \"created\": 1447417062
+        }",
+    ).unwrap();
+    assert_that(&attachment.created.to_rfc3339())
+        .is_equal_to("2015-11-13T12:17:42+00:00".to_string());
+}
+
+#[test]
+fn account_owners() {
+    let account: Account = serde_json::from_str(
+        "{
+            \"id\": \"acc_00009237aqC8c5umZmrRdh\",
+            \"description\": \"Joint account\",
+            \"created\": \"2015-11-13T12:17:42Z\",
+            \"type\": \"uk_retail_joint\",
+            \"owners\": [
+                {
+                    \"user_id\": \"user_00009237aWAIQGs6A3wD3V\",
+                    \"preferred_name\": \"Peter Pan\",
+                    \"preferred_first_name\": \"Peter\"
+                },
+                {
+                    \"user_id\": \"user_00009237aWAIQGs6A3wD3W\",
+                    \"preferred_name\": \"Wendy Darling\",
+                    \"preferred_first_name\": \"Wendy\"
+                }
+            ]
+        }",
+    ).unwrap();
+    assert_that(&account.owners.len()).is_equal_to(2);
+    assert_that(&account.owners[1].preferred_first_name.as_str()).is_equal_to("Wendy");
+
+    let account: Account = serde_json::from_str(
+        "{
+            \"id\": \"acc_00009237aqC8c5umZmrRdh\",
+            \"description\": \"Peter Pan's Account\",
+            \"created\": \"2015-11-13T12:17:42Z\"
+        }",
+    ).unwrap();
+    assert_that(&account.owners).is_empty();
+}
+
+fn declined(id: &str, reason: &str) -> Transaction {
+    let mut json = transaction_json(id, -510, "eating_out", None);
+    json["decline_reason"] = Value::String(reason.to_string());
+    serde_json::from_value(json).unwrap()
+}
+
+#[test]
+fn declines() {
+    let ts = Transactions {
+        transactions: vec![
+            declined("tx_1", "INSUFFICIENT_FUNDS"),
+            transaction("tx_2", -1500, "shopping", None),
+            declined("tx_3", "CARD_BLOCKED"),
+            declined("tx_4", "INSUFFICIENT_FUNDS"),
+        ],
+    };
+    let declines = ts.declines();
+    assert_that(&declines.len()).is_equal_to(3);
+    assert_that(&declines[1].0.id.as_str()).is_equal_to("tx_3");
+    assert_that(declines[1].1).is_equal_to(&DeclineReason::CardBlocked);
+
+    let counts = ts.decline_counts();
+    assert_that(&counts.len()).is_equal_to(2);
+    assert_that(&counts[&DeclineReason::InsufficientFunds]).is_equal_to(2);
+    assert_that(&counts[&DeclineReason::CardBlocked]).is_equal_to(1);
+}
+
+#[test]
+fn is_declined() {
+    assert_that(&declined("tx_1", "CARD_INACTIVE").is_declined()).is_true();
+    assert_that(&transaction("tx_2", -510, "eating_out", None).is_declined()).is_false();
+}
+
+#[test]
+fn transaction_user_id() {
+    let mut json = transaction_json("tx_1", -510, "eating_out", None);
+    json["user_id"] = Value::String("user_00009238aMBIIrS5Rdncq9".to_string());
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&t.user_id)
+        .is_some()
+        .is_equal_to("user_00009238aMBIIrS5Rdncq9".to_string());
+
+    let t = transaction("tx_2", -510, "eating_out", None);
+    assert_that(&t.user_id).is_none();
+}
+
+#[test]
+fn transaction_originator() {
+    let mut json = transaction_json("tx_1", -510, "eating_out", None);
+    json["originator"] = Value::Bool(true);
+    let t: Transaction = serde_json::from_value(json).unwrap();
+    assert_that(&t.originator).is_some().is_true();
+
+    let t = transaction("tx_2", -510, "eating_out", None);
+    assert_that(&t.originator).is_none();
+}
+
+#[test]
+fn category() {
+    let t = transaction("tx_1", -510, "eating_out", None);
+    assert_that(&t.category).is_equal_to(Category::EatingOut);
+    assert_that(&t.category.as_str()).is_equal_to("eating_out");
+
+    let t = transaction("tx_2", -510, "family", None);
+    assert_that(&t.category).is_equal_to(Category::Other("family".to_string()));
+    assert_that(&t.category.as_str()).is_equal_to("family");
+}
+
+#[test]
+fn category_emoji() {
+    assert_that(&Category::EatingOut.emoji()).is_equal_to("🍽");
+    assert_that(&Category::Transport.emoji()).is_equal_to("🚌");
+    assert_that(&Category::Groceries.emoji()).is_equal_to("🛒");
+    assert_that(&Category::Other("family".to_string()).emoji()).is_equal_to("🏷");
+}
+
+#[test]
+fn decline_reason_other() {
+    let t = declined("tx_1", "SOMETHING_NEW");
+    assert_that(&t.decline_reason)
+        .is_some()
+        .is_equal_to(DeclineReason::Other("SOMETHING_NEW".to_string()));
+}